@@ -0,0 +1,1018 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use game_data::{Eval, GameData, MoveData};
+use wdl::WdlModel;
+
+// The length in plies, the total time in milliseconds
+// and the score (see `GameData`) of a game
+#[derive(Debug, Clone)]
+pub struct GameStats {
+    pub length: u32,
+    pub time: u64,
+    pub score10: u32,
+}
+
+// An engine resigns if his eval is worse than `-eval`, he sees
+// himself getting mated within a distance, or his chance of winning
+// is below or of losing is above a percentage, for `count` of his
+// moves with this rule.
+// Moves searched to less than `min_depth` are skipped without
+// breaking the run. A two sided rule also needs the opponent's eval
+// on the move before to agree.
+#[derive(Debug, Clone)]
+pub struct ResignRule {
+    threshold: ResignThreshold,
+    count: u32,
+    min_depth: Option<u32>,
+    two_sided: bool,
+    wdl_model: WdlModel,
+}
+
+#[derive(Debug, Clone)]
+enum ResignThreshold {
+    Eval(i32),
+    Mate(i32),
+    WinPercent(f64),
+    LossPercent(f64),
+}
+
+// An eval with the game ply and the material left in the position
+// that the engine searched, which WDL models need
+#[derive(Debug, Clone, Copy)]
+struct RootEval {
+    eval: Eval,
+    ply: u32,
+    material: u32,
+}
+
+impl RootEval {
+    // Switches the eval to the opponent's point of view
+    fn opponent(self) -> RootEval {
+        RootEval {
+            eval: -self.eval,
+            ..self
+        }
+    }
+}
+
+impl ResignRule {
+    pub fn new(eval: i32, count: u32) -> Result<ResignRule, &'static str> {
+        if eval <= 0 {
+            return Err("eval is out of range.");
+        }
+
+        if count == 0 {
+            return Err("count is out of range.");
+        }
+
+        Ok(ResignRule {
+            threshold: ResignThreshold::Eval(eval),
+            count: count,
+            min_depth: None,
+            two_sided: false,
+            wdl_model: WdlModel::Material,
+        })
+    }
+
+    // Returns a rule that applies once an engine sees himself getting
+    // mated in at most `distance` moves for `count` of his moves
+    pub fn mate(distance: i32, count: u32) -> Result<ResignRule, &'static str> {
+        if distance <= 0 {
+            return Err("distance is out of range.");
+        }
+
+        if count == 0 {
+            return Err("count is out of range.");
+        }
+
+        Ok(ResignRule {
+            threshold: ResignThreshold::Mate(distance),
+            count: count,
+            min_depth: None,
+            two_sided: false,
+            wdl_model: WdlModel::Material,
+        })
+    }
+
+    // Returns a rule that applies once an engine's chance of winning
+    // is below `percent` for `count` of his moves. The chance of
+    // winning is close to 0 in drawn positions as well, so this is
+    // best used with a small `percent` and a long `count`.
+    pub fn win_percent(percent: f64, count: u32) -> Result<ResignRule, &'static str> {
+        if !(percent > 0f64 && percent < 100f64) {
+            return Err("percent is out of range.");
+        }
+
+        if count == 0 {
+            return Err("count is out of range.");
+        }
+
+        Ok(ResignRule {
+            threshold: ResignThreshold::WinPercent(percent),
+            count: count,
+            min_depth: None,
+            two_sided: false,
+            wdl_model: WdlModel::Material,
+        })
+    }
+
+    // Returns a rule that applies once an engine's chance of losing
+    // is above `percent` for `count` of his moves
+    pub fn loss_percent(percent: f64, count: u32) -> Result<ResignRule, &'static str> {
+        if !(percent > 0f64 && percent < 100f64) {
+            return Err("percent is out of range.");
+        }
+
+        if count == 0 {
+            return Err("count is out of range.");
+        }
+
+        Ok(ResignRule {
+            threshold: ResignThreshold::LossPercent(percent),
+            count: count,
+            min_depth: None,
+            two_sided: false,
+            wdl_model: WdlModel::Material,
+        })
+    }
+
+    // Returns a rule that will never be applied
+    pub fn none() -> ResignRule {
+        ResignRule::new(10000, 10000).unwrap()
+    }
+
+    // Returns this rule with moves searched to less than `min_depth`
+    // not counting
+    pub fn with_min_depth(mut self, min_depth: u32) -> ResignRule {
+        self.min_depth = Some(min_depth);
+        self
+    }
+
+    // Returns this rule with moves only counting when the opponent's
+    // eval on the move before shows him winning by the same margin
+    pub fn with_two_sided(mut self) -> ResignRule {
+        self.two_sided = true;
+        self
+    }
+
+    // Returns this rule with chances of winning and losing worked out
+    // by `wdl_model`. It only matters for percentage rules.
+    pub fn with_wdl_model(mut self, wdl_model: WdlModel) -> ResignRule {
+        self.wdl_model = wdl_model;
+        self
+    }
+
+    // Returns whether a move with `eval` counts towards resigning
+    fn is_lost(&self, eval: RootEval) -> bool {
+        match (&self.threshold, eval.eval) {
+            (&ResignThreshold::Eval(threshold), _) => eval.eval.centipawns() <= -threshold,
+            (&ResignThreshold::Mate(threshold), Eval::Mate(distance)) =>
+                distance < 0 && -distance <= threshold,
+            (&ResignThreshold::Mate(_), Eval::Centipawns(_)) => false,
+            (&ResignThreshold::WinPercent(percent), _) => {
+                let wdl = self.wdl_model.wdl(eval.eval, eval.ply, eval.material);
+                100f64 * wdl.win < percent
+            },
+            (&ResignThreshold::LossPercent(percent), _) => {
+                let wdl = self.wdl_model.wdl(eval.eval, eval.ply, eval.material);
+                100f64 * wdl.loss > percent
+            },
+        }
+    }
+
+    // Returns whether a move with `eval` counts towards resigning given
+    // `opponent_eval`, the eval on the move before
+    fn counts(&self, eval: RootEval, opponent_eval: Option<RootEval>) -> bool {
+        if !self.is_lost(eval) {
+            return false;
+        }
+
+        if !self.two_sided {
+            return true;
+        }
+
+        match opponent_eval {
+            Some(opponent_eval) => self.is_lost(opponent_eval.opponent()),
+            None => false,
+        }
+    }
+}
+
+// The game is a draw the score is within [-eval, eval],
+// or the chance of a draw is above a percentage, for
+// `count` of his moves with this rule. The rule
+// may only be applied on or after `from_move`. Moves
+// searched to less than `min_depth` are skipped.
+#[derive(Debug, Clone)]
+pub struct DrawRule {
+    from_move: u32,
+    threshold: DrawThreshold,
+    count: u32,
+    min_depth: Option<u32>,
+    wdl_model: WdlModel,
+}
+
+#[derive(Debug, Clone)]
+enum DrawThreshold {
+    Eval(i32),
+    DrawPercent(f64),
+}
+
+impl DrawRule {
+    pub fn new(from_move: u32, eval: i32, count: u32) -> Result<DrawRule, &'static str> {
+        if from_move == 0 {
+            return Err("from_move is out of range.");
+        }
+
+        if eval < 0 {
+            return Err("eval is out of range.");
+        }
+
+        if count == 0 {
+            return Err("count is out of range.");
+        }
+
+        Ok(DrawRule {
+            from_move: from_move,
+            threshold: DrawThreshold::Eval(eval),
+            count: count,
+            min_depth: None,
+            wdl_model: WdlModel::Material,
+        })
+    }
+
+    // Returns a rule that applies once the chance of a draw is above
+    // `percent` for `count` moves of each side
+    pub fn draw_percent(from_move: u32, percent: f64, count: u32)
+        -> Result<DrawRule, &'static str> {
+
+        if !(percent > 0f64 && percent < 100f64) {
+            return Err("percent is out of range.");
+        }
+
+        let mut rule = DrawRule::new(from_move, 0, count)?;
+        rule.threshold = DrawThreshold::DrawPercent(percent);
+        Ok(rule)
+    }
+
+    // Returns a rule that will never be applied
+    pub fn none() -> DrawRule {
+        DrawRule::new(10000, 0, 10000).unwrap()
+    }
+
+    // Returns this rule with moves searched to less than `min_depth`
+    // not counting
+    pub fn with_min_depth(mut self, min_depth: u32) -> DrawRule {
+        self.min_depth = Some(min_depth);
+        self
+    }
+
+    // Returns this rule with chances of a draw worked out by
+    // `wdl_model`. It only matters for draw percentage rules.
+    pub fn with_wdl_model(mut self, wdl_model: WdlModel) -> DrawRule {
+        self.wdl_model = wdl_model;
+        self
+    }
+
+    // Returns whether a move with `eval` counts towards a draw
+    fn is_drawn(&self, eval: RootEval) -> bool {
+        match self.threshold {
+            DrawThreshold::Eval(threshold) => eval.eval.centipawns().abs() <= threshold,
+            DrawThreshold::DrawPercent(percent) => {
+                let wdl = self.wdl_model.wdl(eval.eval, eval.ply, eval.material);
+                100f64 * wdl.draw > percent
+            },
+        }
+    }
+}
+
+// The game ends once at most `max_pieces` pieces are left, as it
+// would with tablebase adjudication. The result is taken to be the
+// game's actual result, since the tablebases would know it.
+#[derive(Debug, Clone)]
+pub struct TablebaseRule {
+    max_pieces: u32,
+}
+
+impl TablebaseRule {
+    pub fn new(max_pieces: u32) -> Result<TablebaseRule, &'static str> {
+        if max_pieces < 3 {
+            return Err("max_pieces is out of range.");
+        }
+
+        Ok(TablebaseRule { max_pieces: max_pieces })
+    }
+
+    // Returns a rule that will never be applied
+    pub fn none() -> TablebaseRule {
+        TablebaseRule { max_pieces: 0 }
+    }
+
+    pub fn max_pieces(&self) -> u32 {
+        self.max_pieces
+    }
+}
+
+// Returns whether a move was searched deep enough to count towards
+// a rule. Moves from formats without depths always are.
+fn is_deep_enough(move_data: &MoveData, min_depth: Option<u32>) -> bool {
+    match (move_data.depth, min_depth) {
+        (Some(depth), Some(min_depth)) => depth >= min_depth,
+        _ => true,
+    }
+}
+
+// Parses a `depth=<n>` rule option
+fn parse_depth_option(option: &str) -> Option<u32> {
+    match option.strip_prefix("depth=") {
+        Some(value) => value.parse::<u32>().ok(),
+        None => None,
+    }
+}
+
+// Parses a `wdl=ply` or `wdl=material` rule option
+fn parse_wdl_option(option: &str) -> Option<WdlModel> {
+    match option {
+        "wdl=ply" => Some(WdlModel::Ply),
+        "wdl=material" => Some(WdlModel::Material),
+        _ => None,
+    }
+}
+
+impl fmt::Display for ResignRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.threshold {
+            ResignThreshold::Eval(eval) => write!(f, "{}/{}", eval, self.count)?,
+            ResignThreshold::Mate(distance) => write!(f, "M{}/{}", distance, self.count)?,
+            ResignThreshold::WinPercent(percent) => write!(f, "W{}/{}", percent, self.count)?,
+            ResignThreshold::LossPercent(percent) => write!(f, "L{}/{}", percent, self.count)?,
+        }
+
+        if let Some(min_depth) = self.min_depth {
+            write!(f, ",depth={}", min_depth)?;
+        }
+
+        if self.two_sided {
+            write!(f, ",twosided")?;
+        }
+
+        if self.wdl_model == WdlModel::Ply {
+            write!(f, ",wdl=ply")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ResignRuleParsingError {
+    BadFormat,
+    NonPositiveEval,
+    NonPositiveCount,
+    PercentOutOfRange,
+}
+
+// Parses a resign rule in format <eval>/<count>, M<moves>/<count>,
+// W<percent>/<count>, L<percent>/<count> or 'none'. The rule may be followed by options
+// like `,depth=12`, `,twosided` and `,wdl=ply`.
+impl FromStr for ResignRule {
+    type Err = ResignRuleParsingError;
+
+    fn from_str(input: &str) -> Result<ResignRule, ResignRuleParsingError> {
+        parse_resign_rule(input)
+    }
+}
+
+fn parse_resign_rule(input: &str) -> Result<ResignRule, ResignRuleParsingError> {
+    if input == "none" {
+        return Ok(ResignRule::none());
+    }
+
+    let mut parts = input.split(',');
+
+    let args: Vec<&str> = parts.next().unwrap().split('/').collect();
+    if args.len() != 2 {
+        return Err(ResignRuleParsingError::BadFormat);
+    }
+
+    let threshold = if args[0].starts_with('W') || args[0].starts_with('L') {
+        let percent = match args[0][1..].parse::<f64>() {
+            Ok(value) => value,
+            Err(_) => { return Err(ResignRuleParsingError::BadFormat); }
+        };
+
+        if !(percent > 0f64 && percent < 100f64) {
+            return Err(ResignRuleParsingError::PercentOutOfRange);
+        }
+
+        if args[0].starts_with('W') {
+            ResignThreshold::WinPercent(percent)
+        } else {
+            ResignThreshold::LossPercent(percent)
+        }
+    } else {
+        let is_mate = args[0].starts_with('M');
+        let eval_arg = if is_mate { &args[0][1..] } else { args[0] };
+
+        let eval = match eval_arg.parse::<i32>() {
+            Ok(value) => value,
+            Err(_) => { return Err(ResignRuleParsingError::BadFormat); }
+        };
+
+        if eval <= 0 {
+            return Err(ResignRuleParsingError::NonPositiveEval);
+        }
+
+        if is_mate { ResignThreshold::Mate(eval) } else { ResignThreshold::Eval(eval) }
+    };
+
+    let count = match args[1].parse::<u32>() {
+        Ok(value) => value,
+        Err(_) => { return Err(ResignRuleParsingError::BadFormat); }
+    };
+
+    if count == 0 {
+        return Err(ResignRuleParsingError::NonPositiveCount);
+    }
+
+    let mut rule = match threshold {
+        ResignThreshold::Eval(eval) => ResignRule::new(eval, count),
+        ResignThreshold::Mate(distance) => ResignRule::mate(distance, count),
+        ResignThreshold::WinPercent(percent) => ResignRule::win_percent(percent, count),
+        ResignThreshold::LossPercent(percent) => ResignRule::loss_percent(percent, count),
+    }.unwrap();
+
+    for option in parts {
+        if option == "twosided" {
+            rule = rule.with_two_sided();
+            continue;
+        }
+
+        if let Some(wdl_model) = parse_wdl_option(option) {
+            rule = rule.with_wdl_model(wdl_model);
+            continue;
+        }
+
+        match parse_depth_option(option) {
+            Some(min_depth) => { rule = rule.with_min_depth(min_depth); },
+            None => { return Err(ResignRuleParsingError::BadFormat); }
+        }
+    }
+
+    Ok(rule)
+}
+
+impl fmt::Display for DrawRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.threshold {
+            DrawThreshold::Eval(eval) =>
+                write!(f, "{}:{}/{}", self.from_move, eval, self.count)?,
+            DrawThreshold::DrawPercent(percent) =>
+                write!(f, "{}:D{}/{}", self.from_move, percent, self.count)?,
+        }
+
+        if let Some(min_depth) = self.min_depth {
+            write!(f, ",depth={}", min_depth)?;
+        }
+
+        if self.wdl_model == WdlModel::Ply {
+            write!(f, ",wdl=ply")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DrawRuleParsingError {
+    BadFormat,
+    NonPositiveFromMove,
+    NegativeEval,
+    NonPositiveCount,
+    PercentOutOfRange,
+}
+
+// Parses a draw rule in format <move_number>:<eval>/<count>,
+// <move_number>:D<percent>/<count> or 'none'. The rule may be
+// followed by options like `,depth=12` and `,wdl=ply`.
+impl FromStr for DrawRule {
+    type Err = DrawRuleParsingError;
+
+    fn from_str(input: &str) -> Result<DrawRule, DrawRuleParsingError> {
+        parse_draw_rule(input)
+    }
+}
+
+fn parse_draw_rule(input: &str) -> Result<DrawRule, DrawRuleParsingError> {
+    if input == "none" {
+        return Ok(DrawRule::none());
+    }
+
+    let mut parts = input.split(',');
+
+    let args1: Vec<&str> = parts.next().unwrap().split(':').collect();
+    if args1.len() != 2 {
+        return Err(DrawRuleParsingError::BadFormat);
+    }
+
+    let from_move = match args1[0].parse::<u32>() {
+        Ok(value) => value,
+        Err(_) => { return Err(DrawRuleParsingError::BadFormat); }
+    };
+
+    if from_move == 0 {
+        return Err(DrawRuleParsingError::NonPositiveFromMove);
+    }
+
+    let args2: Vec<&str> = args1[1].split('/').collect();
+    if args2.len() != 2 {
+        return Err(DrawRuleParsingError::BadFormat);
+    }
+
+    let threshold = if let Some(percent_arg) = args2[0].strip_prefix('D') {
+        let percent = match percent_arg.parse::<f64>() {
+            Ok(value) => value,
+            Err(_) => { return Err(DrawRuleParsingError::BadFormat); }
+        };
+
+        if !(percent > 0f64 && percent < 100f64) {
+            return Err(DrawRuleParsingError::PercentOutOfRange);
+        }
+
+        DrawThreshold::DrawPercent(percent)
+    } else {
+        let eval = match args2[0].parse::<i32>() {
+            Ok(value) => value,
+            Err(_) => { return Err(DrawRuleParsingError::BadFormat); }
+        };
+
+        if eval <= 0 {
+            return Err(DrawRuleParsingError::NegativeEval);
+        }
+
+        DrawThreshold::Eval(eval)
+    };
+
+    let count = match args2[1].parse::<u32>() {
+        Ok(value) => value,
+        Err(_) => { return Err(DrawRuleParsingError::BadFormat); }
+    };
+
+    if count == 0 {
+        return Err(DrawRuleParsingError::NonPositiveCount);
+    }
+
+    let mut rule = match threshold {
+        DrawThreshold::Eval(eval) => DrawRule::new(from_move, eval, count),
+        DrawThreshold::DrawPercent(percent) => DrawRule::draw_percent(from_move, percent, count),
+    }.unwrap();
+
+    for option in parts {
+        if let Some(wdl_model) = parse_wdl_option(option) {
+            rule = rule.with_wdl_model(wdl_model);
+            continue;
+        }
+
+        match parse_depth_option(option) {
+            Some(min_depth) => { rule = rule.with_min_depth(min_depth); },
+            None => { return Err(DrawRuleParsingError::BadFormat); }
+        }
+    }
+
+    Ok(rule)
+}
+
+pub fn adjudicate_game(
+    game: &GameData,
+    resign_rule: &ResignRule,
+    draw_rule: &DrawRule) -> AdjudicationOutcome {
+
+    adjudicate_game_with(game, resign_rule, draw_rule, &TablebaseRule::none(), RulePriority::Draw)
+}
+
+// Adjudicates with `tablebase_rule` as well as the resign and draw
+// rules. The tablebase rule is checked first since it's never wrong.
+// `priority` picks the rule that's applied when the resign and draw
+// rules both apply on the same ply.
+pub fn adjudicate_game_with(
+    game: &GameData,
+    resign_rule: &ResignRule,
+    draw_rule: &DrawRule,
+    tablebase_rule: &TablebaseRule,
+    priority: RulePriority) -> AdjudicationOutcome {
+
+    let mut resign_counts: [u32; 2] = [0, 0];
+    let mut draw_count = 0;
+
+    let score10 = game.score10;
+
+    // The plies before the first move when counting from White's
+    // first move, for games set up from a FEN
+    let start = &game.start_position;
+    let ply_offset = 2 * (start.fullmove_number.max(1) - 1) as usize +
+                     start.side_to_move.index();
+    let start_material = start.material[0] + start.material[1];
+
+    // The eval of the move at `ply0` with the position the engine
+    // searched, which is the one before the move
+    let root_eval = |ply0: usize| {
+        game.move_data[ply0].eval.map(|eval| RootEval {
+            eval: eval,
+            ply: (ply_offset + ply0) as u32,
+            material: if ply0 == 0 {
+                start_material
+            } else {
+                game.positions[ply0 - 1].material as u32
+            },
+        })
+    };
+
+    let mut total_time = 0;
+    let mut rule_applied: Option<RuleType> = None;
+    let mut both_rules_applied = false;
+    let mut adjudicated_outcome: Option<GameStats> = None;
+
+    for (ply0, move_data) in game.move_data.iter().enumerate() {
+        total_time += move_data.time as u64;
+
+        // Even for White's moves and odd for Black's
+        let game_ply0 = ply_offset + ply0;
+
+        if adjudicated_outcome.is_none() {
+
+            if let Some(position) = game.positions.get(ply0) {
+                if position.piece_count as u32 <= tablebase_rule.max_pieces {
+                    rule_applied = Some(RuleType::Tablebase);
+                    adjudicated_outcome = Some(GameStats {
+                        length: ply0 as u32 + 1,
+                        time: total_time,
+                        score10: score10,
+                    });
+                    continue;
+                }
+            }
+
+            // Book moves and moves without an eval don't count
+            // towards either rule
+            let eval = match root_eval(ply0) {
+                Some(eval) => eval,
+                None => { continue; }
+            };
+
+            let mut draw_applies = false;
+            if is_deep_enough(move_data, draw_rule.min_depth) {
+
+                if draw_rule.is_drawn(eval) {
+                    draw_count += 1;
+                } else {
+                    draw_count = 0;
+                }
+
+                draw_applies = (game_ply0 as u32 + 1) / 2 >= draw_rule.from_move &&
+                               draw_count >= 2 * draw_rule.count;
+            }
+
+            let mut resign_applies = false;
+            if is_deep_enough(move_data, resign_rule.min_depth) {
+
+                // A shallow opponent move can't confirm the eval either
+                let opponent_eval = if ply0 > 0 &&
+                                       is_deep_enough(&game.move_data[ply0 - 1],
+                                                      resign_rule.min_depth) {
+                    root_eval(ply0 - 1)
+                } else {
+                    None
+                };
+
+                if resign_rule.counts(eval, opponent_eval) {
+                    resign_counts[game_ply0 % 2] += 1;
+                } else {
+                    resign_counts[game_ply0 % 2] = 0;
+                }
+
+                resign_applies = resign_counts[game_ply0 % 2] == resign_rule.count;
+            }
+
+            let rule = match (draw_applies, resign_applies, priority) {
+                (true, true, RulePriority::Resign) | (false, true, _) => RuleType::Resign,
+                (true, _, _) => RuleType::Draw,
+                (false, false, _) => { continue; }
+            };
+
+            rule_applied = Some(rule);
+            both_rules_applied = draw_applies && resign_applies;
+            adjudicated_outcome = Some(GameStats {
+                length: ply0 as u32 + 1,
+                time: total_time,
+                score10: match rule {
+                    RuleType::Resign => [0, 10][game_ply0 % 2],
+                    _ => 5,
+                },
+            });
+        }
+    }
+
+    let actual_outcome = GameStats {
+        length: game.move_data.len() as u32,
+        time: total_time,
+        score10: score10,
+    };
+
+    AdjudicationOutcome {
+        adjudicated: adjudicated_outcome.unwrap_or(actual_outcome.clone()),
+        rule_applied: rule_applied,
+        both_rules_applied: both_rules_applied,
+        actual: actual_outcome,
+        time_known: game.time_known,
+    }
+}
+
+// The draws that the rules of chess allow, rather than an eval
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DrawClaim {
+    FiftyMoves,
+    Repetition,
+    InsufficientMaterial,
+}
+
+// Returns the first ply after which a draw could be claimed under the
+// rules of chess, and why. Insufficient material ends the game
+// without a claim but it's treated the same way.
+pub fn find_draw_claim(game: &GameData) -> Option<(usize, DrawClaim)> {
+    let mut repetitions: HashMap<u64, u32> = HashMap::new();
+    repetitions.insert(game.start_position.key, 1);
+
+    for (ply0, position) in game.positions.iter().enumerate() {
+        if position.is_insufficient_material {
+            return Some((ply0, DrawClaim::InsufficientMaterial));
+        }
+
+        let count = repetitions.entry(position.key).or_insert(0);
+        *count += 1;
+        if *count >= 3 {
+            return Some((ply0, DrawClaim::Repetition));
+        }
+
+        if position.halfmove_clock >= 100 {
+            return Some((ply0, DrawClaim::FiftyMoves));
+        }
+    }
+
+    None
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuleType {
+    Resign,
+    Draw,
+    Tablebase,
+}
+
+// The rule that wins when the resign and draw rules both apply on
+// the same ply
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RulePriority {
+    Resign,
+    Draw,
+}
+
+#[derive(Debug, Clone)]
+pub struct AdjudicationOutcome {
+    pub actual: GameStats,
+    pub rule_applied: Option<RuleType>,
+    // Whether the resign and draw rules both applied on the ply the
+    // game was adjudicated, leaving `RulePriority` to pick one
+    pub both_rules_applied: bool,
+    pub adjudicated: GameStats,
+    // Whether the game's times are all known, see `GameData::time_known`
+    pub time_known: bool,
+}
+
+impl AdjudicationOutcome {
+    pub fn correctly_adjudicated(&self) -> bool {
+        self.actual.score10 == self.adjudicated.score10
+    }
+
+    pub fn time_saved(&self) -> u64 {
+        if self.correctly_adjudicated() && self.time_known {
+            self.actual.time - self.adjudicated.time
+        } else {
+            0
+        }
+    }
+
+    pub fn squared_error10(&self) -> u32 {
+       (self.actual.score10 as i32 - self.adjudicated.score10 as i32).pow(2) as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess_pgn_parser::read_games;
+
+    use board::{Board, Position};
+    use comment_format::CutechessFormat;
+    use game_data::{Eval, GameData, map_single_game_data};
+    use wdl::WdlModel;
+
+    use super::{DrawRule, DrawRuleParsingError, ResignRule, ResignRuleParsingError, RootEval,
+                RulePriority, RuleType, TablebaseRule, adjudicate_game, adjudicate_game_with};
+
+    fn game_from_fen(fen: &str, result: &str, moves: &str) -> GameData {
+        let pgn = format!("[Result \"{}\"]\n[SetUp \"1\"]\n[FEN \"{}\"]\n\n{} {}\n",
+                          result, fen, moves, result);
+        let games = read_games(&pgn).unwrap();
+        map_single_game_data(&games[0], &CutechessFormat::new()).unwrap()
+    }
+
+    fn root_eval(eval: Eval, position: &Position) -> RootEval {
+        RootEval {
+            eval: eval,
+            ply: 40,
+            material: position.material[0] + position.material[1],
+        }
+    }
+
+    #[test]
+    fn resign_rule_parsing() {
+        assert_eq!("250/3".parse::<ResignRule>().unwrap().to_string(), "250/3");
+        assert_eq!("M5/2".parse::<ResignRule>().unwrap().to_string(), "M5/2");
+        assert_eq!("250".parse::<ResignRule>().unwrap_err(), ResignRuleParsingError::BadFormat);
+        assert_eq!("250/3,depth=12".parse::<ResignRule>().unwrap().to_string(), "250/3,depth=12");
+        assert_eq!("M/2".parse::<ResignRule>().unwrap_err(), ResignRuleParsingError::BadFormat);
+        assert_eq!("250/3,depth".parse::<ResignRule>().unwrap_err(), ResignRuleParsingError::BadFormat);
+        assert_eq!("M0/2".parse::<ResignRule>().unwrap_err(), ResignRuleParsingError::NonPositiveEval);
+        assert_eq!("0/3".parse::<ResignRule>().unwrap_err(), ResignRuleParsingError::NonPositiveEval);
+        assert_eq!("250/0".parse::<ResignRule>().unwrap_err(), ResignRuleParsingError::NonPositiveCount);
+    }
+
+    #[test]
+    fn draw_rule_parsing() {
+        assert_eq!("34:30/8".parse::<DrawRule>().unwrap().to_string(), "34:30/8");
+        assert_eq!("34:30/8,depth=12".parse::<DrawRule>().unwrap().to_string(), "34:30/8,depth=12");
+        assert_eq!("34/8".parse::<DrawRule>().unwrap_err(), DrawRuleParsingError::BadFormat);
+        assert_eq!("34:30/8,deep".parse::<DrawRule>().unwrap_err(), DrawRuleParsingError::BadFormat);
+        assert_eq!("0:30/8".parse::<DrawRule>().unwrap_err(), DrawRuleParsingError::NonPositiveFromMove);
+        assert_eq!("34:30/0".parse::<DrawRule>().unwrap_err(), DrawRuleParsingError::NonPositiveCount);
+    }
+
+    #[test]
+    fn mate_resign_rule() {
+        let start = Board::start().position();
+        let is_lost = |rule: &ResignRule, eval| rule.is_lost(root_eval(eval, &start));
+
+        let rule = ResignRule::mate(5, 1).unwrap();
+
+        assert!(is_lost(&rule, Eval::Mate(-5)));
+        assert!(!is_lost(&rule, Eval::Mate(-6)));
+        assert!(!is_lost(&rule, Eval::Mate(3)));
+        assert!(!is_lost(&rule, Eval::Centipawns(-5000)));
+
+        assert!(is_lost(&ResignRule::new(500, 1).unwrap(), Eval::Mate(-40)));
+    }
+
+    #[test]
+    fn two_sided_resign_rule() {
+        assert_eq!("250/3,twosided".parse::<ResignRule>().unwrap().to_string(), "250/3,twosided");
+        assert_eq!("M5/2,depth=12,twosided".parse::<ResignRule>().unwrap().to_string(),
+                   "M5/2,depth=12,twosided");
+
+        let start = Board::start().position();
+        let counts = |rule: &ResignRule, eval, opponent_eval: Option<Eval>| {
+            rule.counts(root_eval(eval, &start),
+                        opponent_eval.map(|opponent_eval| root_eval(opponent_eval, &start)))
+        };
+
+        let rule = ResignRule::new(250, 1).unwrap();
+        assert!(counts(&rule, Eval::Centipawns(-300), Some(Eval::Centipawns(100))));
+
+        let rule = rule.with_two_sided();
+        assert!(counts(&rule, Eval::Centipawns(-300), Some(Eval::Centipawns(260))));
+        assert!(counts(&rule, Eval::Centipawns(-300), Some(Eval::Mate(7))));
+        assert!(!counts(&rule, Eval::Centipawns(-300), Some(Eval::Centipawns(100))));
+        assert!(!counts(&rule, Eval::Centipawns(-300), None));
+        assert!(!counts(&rule, Eval::Centipawns(-100), Some(Eval::Centipawns(300))));
+    }
+
+    #[test]
+    fn two_sided_resign_rule_min_depth() {
+        let game = game_from_fen(
+            "4k3/8/8/8/8/8/8/R3K3 w - - 0 30",
+            "1-0",
+            "30. Ra2 {+3.00/5 0.10s} Kd7 {-3.00/20 0.10s} 31. Ra3 {+3.00/20 0.10s} \
+             Kd6 {-3.00/20 0.10s}");
+
+        // Black's first eval isn't confirmed by White's shallow one
+        let rule = "250/1,depth=10,twosided".parse().unwrap();
+        let outcome = adjudicate_game(&game, &rule, &DrawRule::none());
+        assert_eq!(outcome.rule_applied, Some(RuleType::Resign));
+        assert_eq!(outcome.adjudicated.length, 4);
+        assert_eq!(outcome.adjudicated.score10, 10);
+    }
+
+    #[test]
+    fn wdl_rule_parsing() {
+        assert_eq!("L98/3".parse::<ResignRule>().unwrap().to_string(), "L98/3");
+        assert_eq!("L99.5/3,wdl=ply".parse::<ResignRule>().unwrap().to_string(), "L99.5/3,wdl=ply");
+        assert_eq!("L98/3,wdl=material".parse::<ResignRule>().unwrap().to_string(), "L98/3");
+        assert_eq!("L/3".parse::<ResignRule>().unwrap_err(), ResignRuleParsingError::BadFormat);
+        assert_eq!("L98/3,wdl=elo".parse::<ResignRule>().unwrap_err(), ResignRuleParsingError::BadFormat);
+        assert_eq!("L0/3".parse::<ResignRule>().unwrap_err(), ResignRuleParsingError::PercentOutOfRange);
+        assert_eq!("L100/3".parse::<ResignRule>().unwrap_err(), ResignRuleParsingError::PercentOutOfRange);
+        assert_eq!("W2/3,twosided".parse::<ResignRule>().unwrap().to_string(), "W2/3,twosided");
+        assert_eq!("W/3".parse::<ResignRule>().unwrap_err(), ResignRuleParsingError::BadFormat);
+        assert_eq!("W0/3".parse::<ResignRule>().unwrap_err(), ResignRuleParsingError::PercentOutOfRange);
+
+        assert_eq!("40:D90/3".parse::<DrawRule>().unwrap().to_string(), "40:D90/3");
+        assert_eq!("40:D90/3,depth=12,wdl=ply".parse::<DrawRule>().unwrap().to_string(),
+                   "40:D90/3,depth=12,wdl=ply");
+        assert_eq!("40:D/3".parse::<DrawRule>().unwrap_err(), DrawRuleParsingError::BadFormat);
+        assert_eq!("40:D120/3".parse::<DrawRule>().unwrap_err(), DrawRuleParsingError::PercentOutOfRange);
+    }
+
+    #[test]
+    fn wdl_rules() {
+        let start = Board::start().position();
+        let endgame = Board::from_fen("8/5pk1/8/8/8/8/5PK1/3R4 w - - 0 60").unwrap().position();
+
+        let rule = ResignRule::loss_percent(98f64, 1).unwrap();
+        assert!(rule.is_lost(root_eval(Eval::Centipawns(-300), &start)));
+        assert!(!rule.is_lost(root_eval(Eval::Centipawns(-150), &start)));
+        assert!(rule.is_lost(root_eval(Eval::Mate(-5), &start)));
+        assert!(!rule.is_lost(root_eval(Eval::Centipawns(0), &endgame)));
+
+        // A drawn endgame is hardly ever won either, so it counts too
+        let rule = ResignRule::win_percent(2f64, 1).unwrap();
+        assert!(rule.is_lost(root_eval(Eval::Centipawns(-300), &start)));
+        assert!(rule.is_lost(root_eval(Eval::Centipawns(0), &endgame)));
+        assert!(!rule.is_lost(root_eval(Eval::Centipawns(150), &start)));
+
+        // The same small eval is a surer draw with less material left
+        let rule = DrawRule::draw_percent(1, 90f64, 1).unwrap();
+        assert!(!rule.is_drawn(root_eval(Eval::Centipawns(30), &start)));
+        assert!(rule.is_drawn(root_eval(Eval::Centipawns(30), &endgame)));
+        assert!(rule.is_drawn(root_eval(Eval::Centipawns(10), &start)));
+        assert!(!rule.is_drawn(root_eval(Eval::Centipawns(300), &endgame)));
+
+        // The ply model doesn't look at the material
+        let rule = rule.with_wdl_model(WdlModel::Ply);
+        assert!(rule.is_drawn(root_eval(Eval::Centipawns(30), &start)));
+    }
+
+    #[test]
+    fn games_from_fen_with_black_to_move() {
+        let game = game_from_fen(
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "1-0",
+            "1... e5 {-3.00/10 0.10s} 2. Nf3 {+3.00/10 0.10s} Nc6 {-3.00/10 0.10s} \
+             3. Bb5 {+3.00/10 0.10s} a6 {-3.00/10 0.10s}");
+
+        let outcome = adjudicate_game(&game, &ResignRule::new(250, 2).unwrap(), &DrawRule::none());
+        assert_eq!(outcome.rule_applied, Some(RuleType::Resign));
+        assert_eq!(outcome.adjudicated.length, 3);
+        assert_eq!(outcome.adjudicated.score10, 10);
+    }
+
+    #[test]
+    fn draw_rule_from_fen_move_number() {
+        let game = game_from_fen(
+            "4k3/8/8/8/8/8/8/R3K3 w - - 0 30",
+            "1/2-1/2",
+            "30. Ra2 {0.00/10 0.10s} Kd7 {0.00/10 0.10s} 31. Ra3 {0.00/10 0.10s}");
+
+        let outcome = adjudicate_game(&game, &ResignRule::none(), &"30:10/1".parse().unwrap());
+        assert_eq!(outcome.rule_applied, Some(RuleType::Draw));
+        assert_eq!(outcome.adjudicated.length, 2);
+    }
+
+    #[test]
+    fn rule_priority() {
+        let game = game_from_fen(
+            "4k3/8/8/8/8/8/8/R3K3 w - - 0 30",
+            "1-0",
+            "30. Ra2 {+2.00/10 0.10s} Kd7 {-2.00/10 0.10s} 31. Ra3 {+2.00/10 0.10s}");
+
+        let resign_rule = ResignRule::new(150, 1).unwrap();
+        let draw_rule = "30:300/1".parse().unwrap();
+        let adjudicate = |priority| {
+            adjudicate_game_with(&game, &resign_rule, &draw_rule, &TablebaseRule::none(), priority)
+        };
+
+        let outcome = adjudicate(RulePriority::Draw);
+        assert_eq!(outcome.rule_applied, Some(RuleType::Draw));
+        assert_eq!(outcome.adjudicated.length, 2);
+        assert!(outcome.both_rules_applied);
+
+        let outcome = adjudicate(RulePriority::Resign);
+        assert_eq!(outcome.rule_applied, Some(RuleType::Resign));
+        assert_eq!(outcome.adjudicated.length, 2);
+        assert_eq!(outcome.adjudicated.score10, 10);
+        assert!(outcome.both_rules_applied);
+
+        let outcome = adjudicate_game(&game, &resign_rule, &DrawRule::none());
+        assert_eq!(outcome.rule_applied, Some(RuleType::Resign));
+        assert!(!outcome.both_rules_applied);
+    }
+}
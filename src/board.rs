@@ -0,0 +1,674 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use chess_pgn_parser::{Move, Piece, Square};
+
+use game_data::Color;
+
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+// The facts about the position after a move that rules can use
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub side_to_move: Color,
+    // The number of the next move, which starts at 1 and goes up
+    // after each of Black's moves
+    pub fullmove_number: u32,
+    // White's and Black's material, counting pawns as 1, knights and
+    // bishops as 3, rooks as 5 and queens as 9
+    pub material: [u32; 2],
+    // The number of pieces on the board, kings and pawns included
+    pub piece_count: u32,
+    pub is_check: bool,
+    // The number of plies since the last capture or pawn move
+    pub halfmove_clock: u32,
+    // Neither side can mate by any series of moves
+    pub is_insufficient_material: bool,
+    // A hash of everything that makes positions the same for
+    // threefold repetition
+    pub key: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoardError {
+    BadFen,
+    IllegalMove,
+}
+
+// A chess position that SAN moves from PGN games can be played on.
+// Squares are numbered from 0 for a1 to 63 for h8, going along the
+// ranks. Chess960 positions are supported too.
+#[derive(Debug, Clone)]
+pub struct Board {
+    squares: [Option<(Color, Piece)>; 64],
+    side_to_move: Color,
+    // The square of the rook that each side can still castle with,
+    // for White kingside, White queenside, Black kingside and Black
+    // queenside
+    castling: [Option<usize>; 4],
+    en_passant: Option<usize>,
+    halfmove_clock: u32,
+    fullmove_number: u32,
+}
+
+const KNIGHT_STEPS: [(i32, i32); 8] =
+    [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
+const KING_STEPS: [(i32, i32); 8] =
+    [(1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1), (1, -1)];
+const ROOK_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+const BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (-1, 1), (-1, -1), (1, -1)];
+
+impl Board {
+    pub fn start() -> Board {
+        Board::from_fen(START_FEN).unwrap()
+    }
+
+    // Parses a position in Forsyth-Edwards Notation. The move clocks
+    // may be left out, as some GUIs do. Chess960 castling rights may
+    // be given by the rooks' files, as in Shredder-FEN, or by `KQkq`
+    // for the outermost rooks, as in X-FEN.
+    pub fn from_fen(fen: &str) -> Result<Board, BoardError> {
+        let fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 || fields.len() > 6 {
+            return Err(BoardError::BadFen);
+        }
+
+        let mut squares = [None; 64];
+
+        let ranks: Vec<&str> = fields[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(BoardError::BadFen);
+        }
+
+        for (index, rank_text) in ranks.iter().enumerate() {
+            let rank = 7 - index;
+            let mut file = 0;
+
+            for c in rank_text.chars() {
+                if let Some(empty) = c.to_digit(10) {
+                    file += empty as usize;
+                    continue;
+                }
+
+                if file >= 8 {
+                    return Err(BoardError::BadFen);
+                }
+
+                let color = if c.is_uppercase() { Color::White } else { Color::Black };
+                let piece = match c.to_ascii_lowercase() {
+                    'p' => Piece::Pawn,
+                    'n' => Piece::Knight,
+                    'b' => Piece::Bishop,
+                    'r' => Piece::Rook,
+                    'q' => Piece::Queen,
+                    'k' => Piece::King,
+                    _ => { return Err(BoardError::BadFen); }
+                };
+
+                squares[file + 8 * rank] = Some((color, piece));
+                file += 1;
+            }
+
+            if file != 8 {
+                return Err(BoardError::BadFen);
+            }
+        }
+
+        let side_to_move = match fields[1] {
+            "w" => Color::White,
+            "b" => Color::Black,
+            _ => { return Err(BoardError::BadFen); }
+        };
+
+
+        let en_passant = match fields[3] {
+            "-" => None,
+            square => Some(parse_square(square).ok_or(BoardError::BadFen)?),
+        };
+
+        let halfmove_clock = match fields.get(4) {
+            Some(value) => value.parse::<u32>().map_err(|_| BoardError::BadFen)?,
+            None => 0,
+        };
+
+        let fullmove_number = match fields.get(5) {
+            Some(value) => value.parse::<u32>().map_err(|_| BoardError::BadFen)?,
+            None => 1,
+        };
+
+        let mut board = Board {
+            squares: squares,
+            side_to_move: side_to_move,
+            castling: [None; 4],
+            en_passant: en_passant,
+            halfmove_clock: halfmove_clock,
+            fullmove_number: fullmove_number,
+        };
+
+        if fields[2] != "-" {
+            for c in fields[2].chars() {
+                board.add_castling_right(c)?;
+            }
+        }
+
+        for &color in &[Color::White, Color::Black] {
+            let kings = board.squares.iter()
+                .filter(|&&square| square == Some((color, Piece::King)))
+                .count();
+            if kings != 1 {
+                return Err(BoardError::BadFen);
+            }
+        }
+
+        if board.is_in_check(side_to_move.opponent()) {
+            return Err(BoardError::BadFen);
+        }
+
+        Ok(board)
+    }
+
+    // Adds the castling right for a `KQkq` or rook file letter from a
+    // FEN. Rights to castle with pieces that have moved are dropped.
+    fn add_castling_right(&mut self, c: char) -> Result<(), BoardError> {
+        let color = if c.is_uppercase() { Color::White } else { Color::Black };
+        let back_rank = [0, 56][color.index()];
+
+        let king_file = match (0..8).find(|&file| {
+            self.squares[back_rank + file] == Some((color, Piece::King))
+        }) {
+            Some(king_file) => king_file,
+            None => { return Ok(()); }
+        };
+
+        let is_rook = |file: usize| self.squares[back_rank + file] == Some((color, Piece::Rook));
+
+        let (kingside, rook_file) = match c.to_ascii_lowercase() {
+            'k' => (true, (king_file + 1..8).rev().find(|&file| is_rook(file))),
+            'q' => (false, (0..king_file).find(|&file| is_rook(file))),
+            file @ 'a'..='h' => {
+                let file = file as usize - 'a' as usize;
+                (file > king_file, Some(file).filter(|&file| is_rook(file)))
+            },
+            _ => { return Err(BoardError::BadFen); }
+        };
+
+        if let Some(rook_file) = rook_file {
+            self.castling[2 * color.index() + (!kingside) as usize] = Some(back_rank + rook_file);
+        }
+
+        Ok(())
+    }
+
+    pub fn side_to_move(&self) -> Color {
+        self.side_to_move
+    }
+
+    pub fn fullmove_number(&self) -> u32 {
+        self.fullmove_number
+    }
+
+    pub fn position(&self) -> Position {
+        let mut material = [0, 0];
+        let mut piece_count = 0;
+
+        for &(color, piece) in self.squares.iter().flatten() {
+            material[color.index()] += match piece {
+                Piece::Pawn => 1,
+                Piece::Knight | Piece::Bishop => 3,
+                Piece::Rook => 5,
+                Piece::Queen => 9,
+                Piece::King => 0,
+            };
+            piece_count += 1;
+        }
+
+        Position {
+            side_to_move: self.side_to_move,
+            fullmove_number: self.fullmove_number,
+            material: material,
+            piece_count: piece_count,
+            is_check: self.is_in_check(self.side_to_move),
+            halfmove_clock: self.halfmove_clock,
+            is_insufficient_material: self.is_insufficient_material(),
+            key: self.key(),
+        }
+    }
+
+    // Only kings and at most one knight or bishop are left, or only
+    // kings and bishops that are all on squares of the same color
+    fn is_insufficient_material(&self) -> bool {
+        let mut knights = 0;
+        let mut bishops = 0;
+        let mut bishop_square_colors = [false, false];
+
+        for (square, &(_, piece)) in self.squares.iter().enumerate()
+                .filter_map(|(square, piece)| piece.as_ref().map(|piece| (square, piece))) {
+            match piece {
+                Piece::King => { },
+                Piece::Knight => { knights += 1; },
+                Piece::Bishop => {
+                    bishops += 1;
+                    bishop_square_colors[(square % 8 + square / 8) % 2] = true;
+                },
+                _ => { return false; }
+            }
+        }
+
+        knights + bishops <= 1 ||
+        (knights == 0 && !(bishop_square_colors[0] && bishop_square_colors[1]))
+    }
+
+    fn key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+
+        for square in self.squares.iter() {
+            let code = match *square {
+                None => 0,
+                Some((color, piece)) => 1 + 6 * color.index() + piece as usize,
+            };
+            code.hash(&mut hasher);
+        }
+
+        self.side_to_move.index().hash(&mut hasher);
+        self.castling.hash(&mut hasher);
+
+        // The en passant square only matters if a pawn can take on it
+        let en_passant = self.en_passant.filter(|&square| {
+            let backward = [-1, 1][self.side_to_move.index()];
+            [-1, 1].iter().any(|&df| {
+                offset((square % 8) as i32, (square / 8) as i32, df, backward)
+                    .and_then(|from| self.squares[from]) == Some((self.side_to_move, Piece::Pawn))
+            })
+        });
+        en_passant.hash(&mut hasher);
+
+        hasher.finish()
+    }
+
+    // Plays a move for the side to move, returning Err if it isn't
+    // legal or it's ambiguous
+    pub fn play(&mut self, move_: &Move) -> Result<(), BoardError> {
+        let next = match *move_ {
+            Move::CastleKingside => self.castle(true).map_err(|_| BoardError::IllegalMove)?,
+            Move::CastleQueenside => self.castle(false).map_err(|_| BoardError::IllegalMove)?,
+            Move::BasicMove { piece, ref to, ref from, promoted_to, .. } => {
+                let to = square_index(to).ok_or(BoardError::IllegalMove)?;
+                let from_file = from.file().map(|file| file as usize);
+                let from_rank = from.rank().map(|rank| rank as usize);
+
+                let mut candidates = (0..64)
+                    .filter(|&square| from_file.map_or(true, |file| square % 8 == file))
+                    .filter(|&square| from_rank.map_or(true, |rank| square / 8 == rank))
+                    .filter(|&square| self.squares[square] == Some((self.side_to_move, piece)))
+                    .filter(|&square| self.can_move(square, to))
+                    .filter_map(|square| self.moved(square, to, promoted_to).ok())
+                    .filter(|board| !board.is_in_check(self.side_to_move));
+
+                match (candidates.next(), candidates.next()) {
+                    (Some(board), None) => board,
+                    _ => { return Err(BoardError::IllegalMove); }
+                }
+            },
+        };
+
+        *self = next;
+        Ok(())
+    }
+
+    // Returns whether the piece on `from` can move to `to`, not
+    // counting pins or checks
+    fn can_move(&self, from: usize, to: usize) -> bool {
+        let (color, piece) = match self.squares[from] {
+            Some(square) => square,
+            None => { return false; }
+        };
+
+        if let Some((target_color, _)) = self.squares[to] {
+            if target_color == color {
+                return false;
+            }
+        }
+
+        let df = (to % 8) as i32 - (from % 8) as i32;
+        let dr = (to / 8) as i32 - (from / 8) as i32;
+
+        match piece {
+            Piece::Pawn => {
+                let forward = [1, -1][color.index()];
+                let start_rank = [1, 6][color.index()];
+
+                if df == 0 {
+                    self.squares[to].is_none() && (
+                        dr == forward ||
+                        (dr == 2 * forward && from / 8 == start_rank &&
+                         self.squares[(from as i32 + 8 * forward) as usize].is_none()))
+                } else {
+                    df.abs() == 1 && dr == forward &&
+                    (self.squares[to].is_some() || self.en_passant == Some(to))
+                }
+            },
+            Piece::Knight => KNIGHT_STEPS.contains(&(df, dr)),
+            Piece::King => KING_STEPS.contains(&(df, dr)),
+            Piece::Bishop => df.abs() == dr.abs() && self.is_path_clear(from, to),
+            Piece::Rook => (df == 0 || dr == 0) && self.is_path_clear(from, to),
+            Piece::Queen =>
+                (df.abs() == dr.abs() || df == 0 || dr == 0) && self.is_path_clear(from, to),
+        }
+    }
+
+    // Returns whether the squares between `from` and `to`, which must
+    // be on the same line, are empty
+    fn is_path_clear(&self, from: usize, to: usize) -> bool {
+        let step_file = ((to % 8) as i32 - (from % 8) as i32).signum();
+        let step_rank = ((to / 8) as i32 - (from / 8) as i32).signum();
+        let step = step_file + 8 * step_rank;
+
+        let mut square = from as i32 + step;
+        while square != to as i32 {
+            if self.squares[square as usize].is_some() {
+                return false;
+            }
+            square += step;
+        }
+
+        true
+    }
+
+    // Returns the board after moving the piece on `from` to `to`, which
+    // must be a move it can make. Pawns reaching the last rank must be
+    // promoted and others mustn't.
+    fn moved(&self, from: usize, to: usize, promoted_to: Option<Piece>) -> Result<Board, ()> {
+        let (color, piece) = self.squares[from].unwrap();
+        let mut board = self.clone();
+
+        let is_capture = self.squares[to].is_some();
+        let is_promotion = piece == Piece::Pawn && (to / 8 == 0 || to / 8 == 7);
+
+        board.squares[to] = match (is_promotion, promoted_to) {
+            (false, None) => Some((color, piece)),
+            (true, Some(Piece::Knight)) | (true, Some(Piece::Bishop)) |
+            (true, Some(Piece::Rook)) | (true, Some(Piece::Queen)) =>
+                Some((color, promoted_to.unwrap())),
+            _ => { return Err(()); }
+        };
+        board.squares[from] = None;
+
+        // An en passant capture takes the pawn behind `to`
+        if piece == Piece::Pawn && Some(to) == self.en_passant && !is_capture {
+            let captured = if color == Color::White { to - 8 } else { to + 8 };
+            board.squares[captured] = None;
+        }
+
+        board.en_passant = if piece == Piece::Pawn && (to as i32 - from as i32).abs() == 16 {
+            Some((from + to) / 2)
+        } else {
+            None
+        };
+
+        for (index, right) in board.castling.iter_mut().enumerate() {
+            let is_king_move = piece == Piece::King && color.index() == index / 2;
+            if is_king_move || *right == Some(from) || *right == Some(to) {
+                *right = None;
+            }
+        }
+
+        board.halfmove_clock = if piece == Piece::Pawn || is_capture {
+            0
+        } else {
+            self.halfmove_clock + 1
+        };
+
+        board.finish_move();
+        Ok(board)
+    }
+
+    // The king ends up on the g or c file and the rook next to it on
+    // the f or d file, wherever they started, as in Chess960
+    fn castle(&self, kingside: bool) -> Result<Board, ()> {
+        let color = self.side_to_move;
+        let rook = match self.castling[2 * color.index() + (!kingside) as usize] {
+            Some(rook) => rook,
+            None => { return Err(()); }
+        };
+
+        let back_rank = [0, 56][color.index()];
+        let king = match (back_rank..back_rank + 8)
+            .find(|&square| self.squares[square] == Some((color, Piece::King))) {
+            Some(king) => king,
+            None => { return Err(()); }
+        };
+        let (king_to, rook_to) = if kingside {
+            (back_rank + 6, back_rank + 5)
+        } else {
+            (back_rank + 2, back_rank + 3)
+        };
+
+        // Every square the king and rook cross must be empty apart
+        // from the king and rook themselves
+        let low = *[king, rook, king_to, rook_to].iter().min().unwrap();
+        let high = *[king, rook, king_to, rook_to].iter().max().unwrap();
+        if (low..high + 1).any(|square| {
+            square != king && square != rook && self.squares[square].is_some()
+        }) {
+            return Err(());
+        }
+
+        // The king can't castle out of, through or into check
+        let (low, high) = (king.min(king_to), king.max(king_to));
+        if (low..high + 1).any(|square| self.is_attacked(square, color.opponent())) {
+            return Err(());
+        }
+
+        let mut board = self.clone();
+        board.squares[king] = None;
+        board.squares[rook] = None;
+        board.squares[king_to] = Some((color, Piece::King));
+        board.squares[rook_to] = Some((color, Piece::Rook));
+        board.castling[2 * color.index()] = None;
+        board.castling[2 * color.index() + 1] = None;
+        board.en_passant = None;
+        board.halfmove_clock += 1;
+
+        board.finish_move();
+        Ok(board)
+    }
+
+    fn finish_move(&mut self) {
+        if self.side_to_move == Color::Black {
+            self.fullmove_number += 1;
+        }
+        self.side_to_move = self.side_to_move.opponent();
+    }
+
+    fn is_in_check(&self, color: Color) -> bool {
+        match self.squares.iter().position(|&square| square == Some((color, Piece::King))) {
+            Some(king) => self.is_attacked(king, color.opponent()),
+            None => false,
+        }
+    }
+
+    // Returns whether any piece of `by` attacks `square`
+    fn is_attacked(&self, square: usize, by: Color) -> bool {
+        let file = (square % 8) as i32;
+        let rank = (square / 8) as i32;
+
+        let piece_at = |df: i32, dr: i32| {
+            offset(file, rank, df, dr).and_then(|square| self.squares[square])
+        };
+
+        let backward = [-1, 1][by.index()];
+        if piece_at(-1, backward) == Some((by, Piece::Pawn)) ||
+           piece_at(1, backward) == Some((by, Piece::Pawn)) {
+            return true;
+        }
+
+        if KNIGHT_STEPS.iter().any(|&(df, dr)| piece_at(df, dr) == Some((by, Piece::Knight))) ||
+           KING_STEPS.iter().any(|&(df, dr)| piece_at(df, dr) == Some((by, Piece::King))) {
+            return true;
+        }
+
+        let sliders = [(&ROOK_DIRECTIONS, Piece::Rook), (&BISHOP_DIRECTIONS, Piece::Bishop)];
+        for &(directions, slider) in sliders.iter() {
+            for &(df, dr) in directions.iter() {
+                let mut distance = 1;
+                while let Some(target) = offset(file, rank, df * distance, dr * distance) {
+                    match self.squares[target] {
+                        None => { distance += 1; },
+                        Some((color, piece)) => {
+                            if color == by && (piece == slider || piece == Piece::Queen) {
+                                return true;
+                            }
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        false
+    }
+}
+
+// Returns the square `df` files and `dr` ranks away, or None if it's
+// off the board
+fn offset(file: i32, rank: i32, df: i32, dr: i32) -> Option<usize> {
+    let (file, rank) = (file + df, rank + dr);
+    if !(0..8).contains(&file) || !(0..8).contains(&rank) {
+        None
+    } else {
+        Some((file + 8 * rank) as usize)
+    }
+}
+
+fn square_index(square: &Square) -> Option<usize> {
+    match (square.file(), square.rank()) {
+        (Some(file), Some(rank)) => Some(file as usize + 8 * rank as usize),
+        _ => None,
+    }
+}
+
+// Parses a square like `e3`
+fn parse_square(square: &str) -> Option<usize> {
+    let chars: Vec<char> = square.chars().collect();
+    if chars.len() != 2 || !('a'..='h').contains(&chars[0]) || !('1'..='8').contains(&chars[1]) {
+        return None;
+    }
+
+    Some((chars[0] as usize - 'a' as usize) + 8 * (chars[1] as usize - '1' as usize))
+}
+
+#[cfg(test)]
+mod tests {
+    use chess_pgn_parser::read_games;
+
+    use game_data::Color;
+
+    use super::{Board, BoardError};
+
+    // Plays the moves of `pgn` from `fen` and returns the final board
+    fn play(fen: &str, pgn: &str) -> Result<Board, BoardError> {
+        let games = read_games(&format!("[Result \"*\"]\n\n{} *\n", pgn)).unwrap();
+        let mut board = Board::from_fen(fen)?;
+
+        for move_ in &games[0].moves {
+            board.play(&move_.move_.move_)?;
+        }
+
+        Ok(board)
+    }
+
+    #[test]
+    fn board_replay() {
+        let board = play(super::START_FEN,
+                         "1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 4. Bxc6 dxc6 5. O-O f6").unwrap();
+        let position = board.position();
+        assert_eq!(position.side_to_move, Color::White);
+        assert_eq!(position.material, [36, 36]);
+        assert_eq!(position.piece_count, 30);
+        assert!(!position.is_check);
+        assert_eq!(position.halfmove_clock, 0);
+        assert_eq!(board.fullmove_number(), 6);
+
+        let position = play(super::START_FEN, "1. e4 f5 2. Qh5+").unwrap().position();
+        assert_eq!(position.side_to_move, Color::Black);
+        assert!(position.is_check);
+
+        // En passant and promotion
+        let board = play("4k3/1P6/8/8/5p2/8/4P3/4K3 w - - 0 1",
+                         "1. e4 fxe3 2. b8=Q+ Kd7 3. Qb5+ Ke6").unwrap();
+        let position = board.position();
+        assert_eq!(position.material, [9, 1]);
+        assert_eq!(position.halfmove_clock, 3);
+    }
+
+    #[test]
+    fn draw_facts() {
+        let start = Board::start().position();
+        let board = play(super::START_FEN, "1. Nf3 Nf6 2. Ng1 Ng8").unwrap();
+        assert_eq!(board.position().key, start.key);
+        assert_eq!(board.position().halfmove_clock, 4);
+
+        // The same pieces but White can't castle any more
+        let board = play(super::START_FEN, "1. Nf3 Nf6 2. Rg1 Ng8 3. Rh1 Nf6 4. Ng1 Ng8").unwrap();
+        assert!(board.position().key != start.key);
+
+        assert!(!start.is_insufficient_material);
+        let insufficient = |fen| Board::from_fen(fen).unwrap().position().is_insufficient_material;
+        assert!(insufficient("4k3/8/8/8/8/8/8/4K3 w - - 0 1"));
+        assert!(insufficient("4k3/8/8/8/8/8/8/4KN2 w - - 0 1"));
+        assert!(insufficient("2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1"));
+        assert!(!insufficient("3bk3/8/8/8/8/8/8/4KB2 w - - 0 1"));
+        assert!(!insufficient("4k3/8/8/8/8/8/8/3NKN2 w - - 0 1"));
+        assert!(!insufficient("4k3/8/8/8/8/8/8/4KP2 w - - 0 1"));
+    }
+
+    #[test]
+    fn illegal_moves() {
+        assert!(play(super::START_FEN, "1. e5").is_err());
+        assert!(play(super::START_FEN, "1. Nd4").is_err());
+        assert!(play(super::START_FEN, "1. O-O").is_err());
+        // The knight on e2 is pinned
+        assert!(play("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1", "1. Nc3").is_err());
+        // Both knights can go to d2
+        assert!(play("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", "1. Nd2").is_err());
+        assert!(play("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1", "1. Nbd2").is_ok());
+        // Castling through check
+        assert!(play("4k3/8/8/8/8/8/5r2/4K2R w K - 0 1", "1. O-O").is_err());
+        assert!(play("4k3/8/8/8/8/8/8/4K2R w K - 0 1", "1. O-O").is_ok());
+        // Promotion is required
+        assert!(play("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1", "1. b8").is_err());
+        assert_eq!(play(super::START_FEN, "1. e5").err(), Some(BoardError::IllegalMove));
+    }
+
+    #[test]
+    fn chess960_castling() {
+        // Shredder-FEN rights with the king castling onto its rook's square
+        let board = play("bqnbrkrn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRKRN w GEge - 0 1",
+                         "1. O-O O-O").unwrap();
+        let after = Board::from_fen("bqnbrrkn/pppppppp/8/8/8/8/PPPPPPPP/BQNBRRKN w - - 2 2")
+            .unwrap();
+        assert_eq!(board.position().key, after.position().key);
+
+        // X-FEN rights for the outermost rooks, with the king off the e file
+        let board = play("rk5r/pppppppp/8/8/8/8/PPPPPPPP/RK5R w KQkq - 0 1",
+                         "1. O-O-O O-O-O").unwrap();
+        let after = Board::from_fen("2kr3r/pppppppp/8/8/8/8/PPPPPPPP/2KR3R w - - 2 2").unwrap();
+        assert_eq!(board.position().key, after.position().key);
+        // The bishop on c1 is in the way of the king
+        assert!(play("rk5r/pppppppp/8/8/8/8/PPPPPPPP/RKB4R w KQkq - 0 1", "1. O-O").is_err());
+
+        assert_eq!(Board::from_fen("4k3/8/8/8/8/8/8/4K2R w X - 0 1").err(),
+                   Some(BoardError::BadFen));
+    }
+
+    #[test]
+    fn fen_parsing() {
+        assert!(Board::from_fen("rnb1kbnr/pp1ppppp/8/q1p5/2P5/P7/1P1PPPPP/RNBQKBNR w KQkq -").is_ok());
+        assert!(Board::from_fen("8/8/8/8/8/8/8/8 w - - 0 1").is_err());
+        assert!(Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1").is_err());
+        assert!(Board::from_fen("rnbqkbnr/pppppppp/9/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_err());
+        // The side that isn't to move is in check
+        assert!(Board::from_fen("4k3/8/8/8/8/8/8/4KR2 w - - 0 1").is_ok());
+        assert!(Board::from_fen("4k3/4R3/8/8/8/8/8/4K3 w - - 0 1").is_err());
+    }
+}
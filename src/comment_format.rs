@@ -0,0 +1,401 @@
+use chess_pgn_parser::Game;
+use regex::{Captures,Regex};
+
+use game_data::{Eval, MoveData};
+
+// A comment that isn't in the format it's parsed as
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CommentError;
+
+// A way that GUIs and match runners write the engine's evaluation
+// and the time taken into the comment after each move
+pub trait CommentFormat {
+    // Parses `comment`, the comment after the move at the 0-based `ply`
+    // of `game`. The game is given for formats that need more than a
+    // single comment, like the previous clock time of the same side.
+    fn parse(&self, comment: &str, game: &Game, ply: usize) -> Result<MoveData, CommentError>;
+}
+
+// Returns the comment format called `name`, or None if there isn't one
+pub fn comment_format_by_name(name: &str) -> Option<Box<dyn CommentFormat>> {
+    match name {
+        "auto" => Some(Box::new(AutoFormat::new())),
+        "cutechess" => Some(Box::new(CutechessFormat::new())),
+        "lichess" => Some(Box::new(LichessFormat::new())),
+        _ => None,
+    }
+}
+
+// Tries each known format in turn and uses the first one that can
+// parse the comment
+pub struct AutoFormat {
+    formats: Vec<Box<dyn CommentFormat>>,
+}
+
+impl AutoFormat {
+    pub fn new() -> AutoFormat {
+        AutoFormat {
+            formats: vec![
+                Box::new(CutechessFormat::new()),
+                Box::new(LichessFormat::new()),
+            ]
+        }
+    }
+}
+
+impl Default for AutoFormat {
+    fn default() -> AutoFormat {
+        AutoFormat::new()
+    }
+}
+
+impl CommentFormat for AutoFormat {
+    fn parse(&self, comment: &str, game: &Game, ply: usize) -> Result<MoveData, CommentError> {
+        for format in &self.formats {
+            if let Ok(move_data) = format.parse(comment, game, ply) {
+                return Ok(move_data);
+            }
+        }
+
+        Err(CommentError)
+    }
+}
+
+// The cutechess-cli format, e.g. `+0.18/15 0.45s`. fastchess and newer
+// cutechess builds may add search statistics after the time, like
+// `+0.25/12 0.123s, n=12345, sd=20, nps=100365, tb=0`.
+pub struct CutechessFormat {
+    re: Regex
+}
+
+impl CutechessFormat {
+    pub fn new() -> CutechessFormat {
+        let re = Regex::new(r"(?x)
+                ^(?P<sign>(-|\+)?)
+                ((?P<mate>M\d+)|((?P<eval>\d+)(\.(?P<eval_dec>\d{2}))))
+                /(?P<depth>\d+),?\s
+                ((?P<time>\d+)(\.(?P<time_dec>\d{1,3}))?s)
+            ").unwrap();
+
+        CutechessFormat { re: re }
+    }
+
+    fn parse_comment(&self, comment: &str) -> Result<MoveData, CommentError> {
+
+        let captures_opt = self.re.captures(comment);
+        if captures_opt.is_none() {
+            return Err(CommentError);
+        }
+
+        let captures = captures_opt.unwrap();
+        let eval = CutechessFormat::get_eval(&captures);
+        let time = CutechessFormat::get_time(&captures);
+
+        let mut move_data = MoveData::new(eval, time);
+        move_data.depth = captures.name("depth").and_then(|depth| depth.parse::<u32>().ok());
+
+        let (_, end) = captures.pos(0).unwrap();
+        CutechessFormat::parse_statistics(&comment[end..], &mut move_data)?;
+
+        Ok(move_data)
+    }
+
+    // Parses the comma separated `key=value` pairs after the time.
+    // Keys that aren't known, like `pv` or `hashfull`, are skipped.
+    fn parse_statistics(input: &str, move_data: &mut MoveData) -> Result<(), CommentError> {
+        for field in input.split(',') {
+            let args: Vec<&str> = field.trim().splitn(2, '=').collect();
+            if args.len() != 2 {
+                continue;
+            }
+
+            match args[0] {
+                "n" => move_data.nodes = Some(args[1].parse().map_err(|_| CommentError)?),
+                "sd" => move_data.seldepth = Some(args[1].parse().map_err(|_| CommentError)?),
+                "nps" => move_data.nps = Some(args[1].parse().map_err(|_| CommentError)?),
+                "tb" => move_data.tbhits = Some(args[1].parse().map_err(|_| CommentError)?),
+                _ => { },
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_eval(captures: &Captures) -> Eval {
+        let sign = match captures.name("sign") {
+            None | Some("") | Some("+") => 1,
+            Some("-") => -1,
+            _ => unreachable!(),
+        };
+
+        // cutechess gives the distance to mate in plies
+        match captures.name("mate") {
+            None | Some("") => { },
+            Some(value) => {
+                let plies = value[1..].parse::<i32>().unwrap();
+                return Eval::Mate(sign * ((plies + 1) / 2));
+            }
+        }
+
+        let mut result = 0;
+
+        result += match captures.name("eval") {
+            None | Some("") => 0,
+            Some(value) => 100 * value.parse::<i32>().unwrap(),
+        };
+
+        result += match captures.name("eval_dec") {
+            None | Some("") => 0,
+            Some(value) => value.parse::<i32>().unwrap(),
+        };
+
+        Eval::Centipawns(sign * result)
+    }
+
+    fn get_time(captures: &Captures) -> u32 {
+        let mut result = 0;
+
+        result +=
+        match captures.name("time") {
+            Some(value) => 1000 * value.parse::<u32>().unwrap(),
+            _ => unreachable!(),
+        };
+
+        result +=
+        match captures.name("time_dec") {
+            None | Some("") => 0,
+            Some(value) => 10u32.pow((3 - value.len() as i32) as u32) *
+                           value.parse::<u32>().unwrap(),
+        };
+
+        result
+    }
+}
+
+impl Default for CutechessFormat {
+    fn default() -> CutechessFormat {
+        CutechessFormat::new()
+    }
+}
+
+impl CommentFormat for CutechessFormat {
+    fn parse(&self, comment: &str, _game: &Game, _ply: usize) -> Result<MoveData, CommentError> {
+        self.parse_comment(comment)
+    }
+}
+
+// The lichess export format, e.g. `[%eval 0.23] [%clk 0:03:00]`.
+// Evals are from White's point of view and the time for a move is
+// worked out from the clocks before and after it.
+pub struct LichessFormat {
+    eval_re: Regex,
+    clock_re: Regex,
+}
+
+impl LichessFormat {
+    pub fn new() -> LichessFormat {
+        let eval_re = Regex::new(r"(?x)
+                \[%eval\s+
+                ((\#(?P<mate>-?\d+))|(?P<eval>-?\d+(\.\d+)?))
+                \]
+            ").unwrap();
+
+        let clock_re = Regex::new(r"(?x)
+                \[%clk\s+
+                (?P<h>\d+):(?P<m>\d{2}):(?P<s>\d{2}(\.\d+)?)
+                \]
+            ").unwrap();
+
+        LichessFormat {
+            eval_re: eval_re,
+            clock_re: clock_re,
+        }
+    }
+
+    // Returns White's eval
+    fn get_eval(&self, comment: &str) -> Option<Eval> {
+        let captures = match self.eval_re.captures(comment) {
+            Some(captures) => captures,
+            None => { return None; }
+        };
+
+        match (captures.name("mate"), captures.name("eval")) {
+            (Some(mate), _) => Some(Eval::Mate(mate.parse::<i32>().unwrap())),
+            (None, Some(eval)) =>
+                Some(Eval::Centipawns((100f64 * eval.parse::<f64>().unwrap()).round() as i32)),
+            (None, None) => unreachable!(),
+        }
+    }
+
+    // Returns the time left on the clock in milliseconds
+    fn get_clock(&self, comment: &str) -> Option<u32> {
+        let captures = match self.clock_re.captures(comment) {
+            Some(captures) => captures,
+            None => { return None; }
+        };
+
+        let h = captures.name("h").unwrap().parse::<u32>().unwrap();
+        let m = captures.name("m").unwrap().parse::<u32>().unwrap();
+        let s = captures.name("s").unwrap().parse::<f64>().unwrap();
+
+        Some(1000 * (60 * (60 * h + m)) + (1000f64 * s).round() as u32)
+    }
+}
+
+// Returns the base time and increment in milliseconds from a
+// `TimeControl` header like `180+2`
+fn get_time_control(game: &Game) -> Option<(u32, u32)> {
+    let value = match game.tags.iter().find(|tag| tag.0 == "TimeControl") {
+        Some(tag) => &tag.1,
+        None => { return None; }
+    };
+
+    let args: Vec<&str> = value.split('+').collect();
+    let base = match args[0].parse::<u32>() {
+        Ok(base) => base,
+        Err(_) => { return None; }
+    };
+    let increment = match args.get(1).map(|increment| increment.parse::<u32>()) {
+        None => 0,
+        Some(Ok(increment)) => increment,
+        Some(Err(_)) => { return None; }
+    };
+
+    Some((1000 * base, 1000 * increment))
+}
+
+// Returns 1 if Black moves first in `game`, which happens when it
+// starts from a FEN with Black to move, and 0 otherwise
+fn first_mover(game: &Game) -> usize {
+    let fen = game.tags.iter().find(|tag| tag.0 == "FEN");
+    match fen.and_then(|tag| tag.1.split_whitespace().nth(1)) {
+        Some("b") => 1,
+        _ => 0,
+    }
+}
+
+impl CommentFormat for LichessFormat {
+    fn parse(&self, comment: &str, game: &Game, ply: usize) -> Result<MoveData, CommentError> {
+
+        // There's no eval after a mating move
+        let eval = match self.get_eval(comment) {
+            Some(eval) => [eval, -eval][(ply + first_mover(game)) % 2],
+            None if game.moves[ply].move_.is_checkmate => Eval::Mate(0),
+            None => { return Err(CommentError); }
+        };
+
+        let time_control = get_time_control(game);
+        let increment = time_control.map_or(0, |(_, increment)| increment);
+
+        // The increment is only added once a side has moved, so
+        // its first move is timed from the base time alone
+        let clock = self.get_clock(comment);
+        let previous_clock = if ply >= 2 {
+            game.moves[ply - 2].comment.as_ref()
+                .and_then(|comment| self.get_clock(comment))
+                .map(|clock| clock + increment)
+        } else {
+            time_control.map(|(base, _)| base)
+        };
+
+        let time = match (previous_clock, clock) {
+            (Some(previous_clock), Some(clock)) => previous_clock.saturating_sub(clock),
+            _ => 0,
+        };
+
+        Ok(MoveData::new(eval, time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess_pgn_parser::read_games;
+
+    use game_data::Eval;
+
+    use super::{CommentError, CommentFormat, CutechessFormat, LichessFormat};
+
+    #[test]
+    fn cutechess_comment_parsing() {
+       let format = CutechessFormat::new();
+
+       let parse = |comment| {
+           let move_data = format.parse_comment(comment).unwrap();
+           (move_data.eval.unwrap(), move_data.time, move_data.depth)
+       };
+
+       assert_eq!(parse("-1.91/13 0.031s"), (Eval::Centipawns(-191), 31, Some(13)));
+       assert_eq!(parse("+0.18/15 0.45s"), (Eval::Centipawns(18), 450, Some(15)));
+       assert_eq!(parse("+M17/21 0.020s"), (Eval::Mate(9), 20, Some(21)));
+       assert_eq!(parse("-M26/18 0.022s"), (Eval::Mate(-13), 22, Some(18)));
+    }
+
+    #[test]
+    fn fastchess_comment_parsing() {
+       let format = CutechessFormat::new();
+
+       let move_data = format.parse_comment(
+           "+0.25/12 0.123s, n=12345, sd=20, nps=100365, hashfull=5, tb=0, pv=\"e2e4 e7e5\"")
+           .unwrap();
+
+       assert_eq!(move_data.eval, Some(Eval::Centipawns(25)));
+       assert_eq!(move_data.time, 123);
+       assert_eq!(move_data.depth, Some(12));
+       assert_eq!(move_data.nodes, Some(12345));
+       assert_eq!(move_data.seldepth, Some(20));
+       assert_eq!(move_data.nps, Some(100365));
+       assert_eq!(move_data.tbhits, Some(0));
+
+       assert_eq!(format.parse_comment("-0.40/9, 0.010s").unwrap().time, 10);
+       assert_eq!(format.parse_comment("+0.25/12 0.123s, n=lots"), Err(CommentError));
+    }
+
+    #[test]
+    fn lichess_comment_parsing() {
+       let format = LichessFormat::new();
+
+       assert_eq!(format.get_eval(" [%eval 0.23] [%clk 0:03:00] "), Some(Eval::Centipawns(23)));
+       assert_eq!(format.get_eval(" [%eval -1.5] [%clk 0:03:00] "), Some(Eval::Centipawns(-150)));
+       assert_eq!(format.get_eval(" [%eval #-3] [%clk 0:03:00] "), Some(Eval::Mate(-3)));
+       assert_eq!(format.get_eval(" [%eval #12] "), Some(Eval::Mate(12)));
+       assert_eq!(format.get_eval(" [%clk 0:03:00] "), None);
+
+       assert_eq!(format.get_clock(" [%eval 0.23] [%clk 0:03:00] "), Some(180000));
+       assert_eq!(format.get_clock(" [%clk 1:02:03.4] "), Some(3723400));
+       assert_eq!(format.get_clock(" [%eval 0.23] "), None);
+    }
+
+    #[test]
+    fn lichess_move_times() {
+       let format = LichessFormat::new();
+       let games = read_games(concat!(
+           "[TimeControl \"180+2\"]\n[Result \"*\"]\n\n",
+           "1. e4 { [%eval 0.23] [%clk 0:03:00] } 1... e5 { [%eval 0.2] [%clk 0:02:59] } ",
+           "2. Nf3 { [%eval 0.3] [%clk 0:02:57] } *\n")).unwrap();
+       let game = &games[0];
+
+       let time = |ply: usize| {
+           let comment = game.moves[ply].comment.as_ref().unwrap();
+           format.parse(comment, game, ply).unwrap().time
+       };
+
+       // Neither side has been given the increment on its first move
+       assert_eq!(time(0), 0);
+       assert_eq!(time(1), 1000);
+       assert_eq!(time(2), 5000);
+    }
+
+    #[test]
+    fn mate_distances_in_moves() {
+       let cutechess = CutechessFormat::new();
+       let lichess = LichessFormat::new();
+
+       let cutechess_eval = |comment| cutechess.parse_comment(comment).unwrap().eval;
+
+       // Mating in 3 plies is mating on the second move
+       assert_eq!(cutechess_eval("+M3/20 0.10s"), Some(Eval::Mate(2)));
+       assert_eq!(lichess.get_eval(" [%eval #2] "), Some(Eval::Mate(2)));
+       assert_eq!(cutechess_eval("+M1/20 0.10s"), lichess.get_eval(" [%eval #1] "));
+       assert_eq!(cutechess_eval("-M4/20 0.10s"), lichess.get_eval(" [%eval #-2] "));
+    }
+}
@@ -1,16 +1,297 @@
+use std::fmt;
+use std::ops::Neg;
+
 use chess_pgn_parser::{Game, GameTermination};
-use regex::{Captures,Regex};
-use super::{GameData, MoveData};
 
+use board::{Board, Position};
+use comment_format::{AutoFormat, CommentError, CommentFormat};
+
+// An engine's evaluation of the position from its own point of view
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Eval {
+    Centipawns(i32),
+    // Mate in a number of moves, counting only the mating side's
+    // moves, as lichess and UCI engines do. It's positive when the
+    // engine is mating, negative when it's being mated and 0 right
+    // after a mating move.
+    Mate(i32),
+}
+
+impl Eval {
+    // Returns the eval in centipawns, counting mates as +/-10000
+    pub fn centipawns(&self) -> i32 {
+        match *self {
+            Eval::Centipawns(value) => value,
+            Eval::Mate(distance) if distance < 0 => -10000,
+            Eval::Mate(_) => 10000,
+        }
+    }
+}
+
+// Switches the eval to the opponent's point of view
+impl Neg for Eval {
+    type Output = Eval;
+
+    fn neg(self) -> Eval {
+        match self {
+            Eval::Centipawns(value) => Eval::Centipawns(-value),
+            Eval::Mate(distance) => Eval::Mate(-distance),
+        }
+    }
+}
+
+// The evaluation of the engine after the move and the time taken
+// in milliseconds. Some formats also give the engine's search
+// depth and statistics, which are None when they're missing.
+// Opening book moves and moves with only a remark like
+// `White loses on time` have no eval. The remark has no time either,
+// so such a move is given a time of 0 (see `GameData::time_known`).
+#[derive(Debug, PartialEq)]
+pub struct MoveData {
+    pub eval: Option<Eval>,
+    pub time: u32,
+    pub book: bool,
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub seldepth: Option<u32>,
+    pub nps: Option<u64>,
+    pub tbhits: Option<u64>,
+}
+
+impl MoveData {
+    pub fn new(eval: Eval, time: u32) -> MoveData {
+        MoveData {
+            eval: Some(eval),
+            ..MoveData::without_eval(time)
+        }
+    }
+
+    pub fn without_eval(time: u32) -> MoveData {
+        MoveData {
+            eval: None,
+            time: time,
+            book: false,
+            depth: None,
+            nodes: None,
+            seldepth: None,
+            nps: None,
+            tbhits: None,
+        }
+    }
+
+    // Returns the data for a move from the opening book
+    pub fn book() -> MoveData {
+        MoveData {
+            book: true,
+            ..MoveData::without_eval(0)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    White,
+    Black,
+}
+
+impl Color {
+    pub fn opponent(self) -> Color {
+        match self {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        }
+    }
+
+    // Returns 0 for White and 1 for Black, for indexing pairs of values
+    pub fn index(self) -> usize {
+        self as usize
+    }
+}
+
+// Why a game ended, from the remark that GUIs like cutechess
+// leave in the last move's comment
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TerminationReason {
+    Checkmate,
+    Stalemate,
+    Repetition,
+    FiftyMoves,
+    InsufficientMaterial,
+    Agreement,
+    Adjudication,
+    Timeout,
+    Resignation,
+    IllegalMove,
+    Disconnection,
+}
+
+// A remark on how a game ended, e.g. `White loses on time`. `winner`
+// is None for draws.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Termination {
+    pub reason: TerminationReason,
+    pub winner: Option<Color>,
+    pub text: String,
+}
+
+impl Termination {
+    // Parses a remark like `Draw by 3-fold repetition`, returning None
+    // if it isn't a known one
+    pub fn from_remark(remark: &str) -> Option<Termination> {
+        let text = remark.trim();
+
+        let (side, rest) = if let Some(rest) = text.strip_prefix("Draw by ") {
+            (None, rest)
+        } else if let Some(rest) = text.strip_prefix("White") {
+            (Some(Color::White), rest)
+        } else if let Some(rest) = text.strip_prefix("Black") {
+            (Some(Color::Black), rest)
+        } else {
+            return None;
+        };
+
+        let reason = match (side, rest) {
+            (None, "stalemate") => TerminationReason::Stalemate,
+            (None, "3-fold repetition") => TerminationReason::Repetition,
+            (None, "fifty moves rule") => TerminationReason::FiftyMoves,
+            (None, "insufficient mating material") => TerminationReason::InsufficientMaterial,
+            (None, "agreement") => TerminationReason::Agreement,
+            (None, _) if rest.starts_with("adjudication") => TerminationReason::Adjudication,
+            (Some(_), " mates") => TerminationReason::Checkmate,
+            (Some(_), " loses on time") => TerminationReason::Timeout,
+            (Some(_), " resigns") => TerminationReason::Resignation,
+            (Some(_), " disconnects") |
+            (Some(_), "'s connection stalls") => TerminationReason::Disconnection,
+            (Some(_), _) if rest.starts_with(" wins by adjudication") =>
+                TerminationReason::Adjudication,
+            (Some(_), _) if rest.starts_with(" makes an illegal move") =>
+                TerminationReason::IllegalMove,
+            _ => { return None; }
+        };
+
+        // Remarks on a loss name the loser, the others the winner
+        let winner = match reason {
+            TerminationReason::Timeout |
+            TerminationReason::Resignation |
+            TerminationReason::IllegalMove |
+            TerminationReason::Disconnection => side.map(Color::opponent),
+            _ => side,
+        };
+
+        Some(Termination {
+            reason: reason,
+            winner: winner,
+            text: text.to_string(),
+        })
+    }
+
+    // Finds the remark in a comment, which is either the whole comment
+    // or follows the engine's data after a comma
+    pub fn from_comment(comment: &str) -> Option<Termination> {
+        Termination::from_remark(comment).or_else(|| {
+            comment.rfind(',').and_then(|index| Termination::from_remark(&comment[index + 1..]))
+        })
+    }
+}
+
+// The facts about the position after a move that the rules look at.
+// Only these are kept for each ply so that long games stay small.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlyPosition {
+    // A hash of everything that makes positions the same for
+    // threefold repetition
+    pub key: u64,
+    // The number of plies since the last capture or pawn move
+    pub halfmove_clock: u16,
+    // Both sides' material together, counted as in `Position`
+    pub material: u8,
+    // The number of pieces on the board, kings and pawns included
+    pub piece_count: u8,
+    pub is_insufficient_material: bool,
+}
+
+impl PlyPosition {
+    pub fn new(position: &Position) -> PlyPosition {
+        PlyPosition {
+            key: position.key,
+            halfmove_clock: position.halfmove_clock.min(u16::MAX as u32) as u16,
+            material: (position.material[0] + position.material[1]) as u8,
+            piece_count: position.piece_count as u8,
+            is_insufficient_material: position.is_insufficient_material,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct GameData {
+    // We store the score as 10x the usual values and
+    // scale down when displaying results.
+    // 1-0     => 10
+    // 1/2-1/2 => 5
+    // 0-1     => 0
+    pub score10: u32,
+    pub move_data: Vec<MoveData>,
+    // The position before the first move and what the rules need
+    // to know about the one after each move
+    pub start_position: Position,
+    pub positions: Vec<PlyPosition>,
+    pub termination: Option<Termination>,
+    // False if a move's time isn't known because its comment is only
+    // a remark. The game's times then fall short, so they're left out
+    // of the time totals.
+    pub time_known: bool,
+    // The game's PGN header tags in the order they were given
+    pub tags: Vec<(String, String)>,
+}
+
+impl GameData {
+    // Returns the value of the tag `key`, or None if the game doesn't
+    // have it
+    pub fn tag(&self, key: &str) -> Option<&str> {
+        self.tags.iter()
+            .find(|tag| tag.0 == key)
+            .map(|tag| &tag.1[..])
+    }
+
+    pub fn white(&self) -> Option<&str> {
+        self.tag("White")
+    }
+
+    pub fn black(&self) -> Option<&str> {
+        self.tag("Black")
+    }
+}
+
+#[derive(Debug)]
 pub struct GameMappingError {
     pub game_number: u32,
     pub error: GameError,
 }
 
+#[derive(Debug, PartialEq)]
 pub enum GameError {
     UnknownGameTermination,
     MissingComment { ply: u32 },
     BadComment { ply: u32 },
+    BadFen,
+    IllegalMove { ply: u32 },
+}
+
+impl fmt::Display for GameMappingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.error {
+            GameError::UnknownGameTermination =>
+                write!(f, "Game {} has unknown result", self.game_number),
+            GameError::MissingComment { ply } =>
+                write!(f, "Game {}, Ply {} - Missing comment", self.game_number, ply),
+            GameError::BadComment { ply } =>
+                write!(f, "Game {}, Ply {} - Bad comment format", self.game_number, ply),
+            GameError::BadFen =>
+                write!(f, "Game {} has bad FEN", self.game_number),
+            GameError::IllegalMove { ply } =>
+                write!(f, "Game {}, Ply {} - Illegal move", self.game_number, ply),
+        }
+    }
 }
 
 pub fn map_game_data(games: &Vec<Game>)
@@ -18,10 +299,10 @@ pub fn map_game_data(games: &Vec<Game>)
 
     let mut result: Vec<GameData> = Vec::with_capacity(games.len());
 
-    let comment_parser = CommentParser::new();
+    let comment_format = AutoFormat::new();
 
     for (index, game) in games.iter().enumerate() {
-        match map_single_game_data(game, &comment_parser) {
+        match map_single_game_data(game, &comment_format) {
             Ok(game_data) => result.push(game_data),
             Err(error) => {
                 return Err(GameMappingError {
@@ -34,7 +315,7 @@ pub fn map_game_data(games: &Vec<Game>)
     Ok(result)
 }
 
-fn map_single_game_data(game: &Game, comment_parser: &CommentParser) ->
+pub fn map_single_game_data(game: &Game, comment_format: &dyn CommentFormat) ->
     Result<GameData, GameError> {
 
     let score10 = match game.termination {
@@ -46,21 +327,46 @@ fn map_single_game_data(game: &Game, comment_parser: &CommentParser) ->
         }
     };
 
+    // Games that don't start from the usual position give it in
+    // the FEN tag
+    let mut board = match game.tags.iter().find(|tag| tag.0 == "FEN") {
+        Some(tag) => Board::from_fen(&tag.1).map_err(|_| GameError::BadFen)?,
+        None => Board::start(),
+    };
+
+    let start_position = board.position();
+
     let mut move_data_vec : Vec<MoveData> =
         Vec::with_capacity(game.moves.len());
+    let mut positions = Vec::with_capacity(game.moves.len());
+    let mut time_known = true;
 
     for (ply, move_) in game.moves.iter().enumerate() {
 
+        if board.play(&move_.move_.move_).is_err() {
+            return Err(GameError::IllegalMove { ply: ply as u32 });
+        }
+        positions.push(PlyPosition::new(&board.position()));
+
         let comment_opt = move_.comment.as_ref();
         if comment_opt.is_none() {
             return Err(GameError::MissingComment { ply: ply as u32 });
         }
 
         let comment = comment_opt.unwrap();
-        let result = comment_parser.parse(comment);
+        if comment.trim() == "book" {
+            move_data_vec.push(MoveData::book());
+            continue;
+        }
+
+        let result = comment_format.parse(comment, game, ply);
         match result {
             Ok(move_data) => move_data_vec.push(move_data),
-            Err(()) => {
+            Err(CommentError) if Termination::from_remark(comment).is_some() => {
+                move_data_vec.push(MoveData::without_eval(0));
+                time_known = false;
+            },
+            Err(CommentError) => {
                 return Err(GameError::BadComment {
                     ply: (ply + 1) as u32
                 });
@@ -68,101 +374,64 @@ fn map_single_game_data(game: &Game, comment_parser: &CommentParser) ->
         }
     }
 
+    let termination = game.moves.last()
+        .and_then(|move_| move_.comment.as_ref())
+        .and_then(|comment| Termination::from_comment(comment));
+
     Ok(GameData {
         score10: score10,
-        move_data: move_data_vec
+        move_data: move_data_vec,
+        start_position: start_position,
+        positions: positions,
+        termination: termination,
+        time_known: time_known,
+        tags: game.tags.clone(),
     })
 }
 
-struct CommentParser {
-    re: Regex
-}
-
-impl CommentParser {
-    pub fn new() -> CommentParser {
-        let re = Regex::new(r"(?x)
-                ^(?P<sign>(-|\+)?)
-                ((?P<mate>M\d+)|((?P<eval>\d+)(\.(?P<eval_dec>\d{2}))))
-                /\d+\s
-                ((?P<time>\d+)(\.(?P<time_dec>\d{1,3}))?s)
-            ").unwrap();
-
-        CommentParser { re: re }
-    }
-
-    pub fn parse(&self, comment: &str) -> Result<MoveData, ()> {
-
-        let captures_opt = self.re.captures(comment);
-        if captures_opt.is_none() {
-            return Err(());
-        }
-
-        let captures = captures_opt.unwrap();
-        let eval = CommentParser::get_eval(&captures);
-        let time = CommentParser::get_time(&captures);
-
-        Ok(MoveData { eval: eval, time: time })
-    }
-
-    fn get_eval(captures: &Captures) -> i32 {
-        let mut result = 0;
-
-        result += match captures.name("mate") {
-            None | Some("") => 0,
-            Some(_) => 10000,
-        };
-
-        result += match captures.name("eval") {
-            None | Some("") => 0,
-            Some(value) => 100 * value.parse::<i32>().unwrap(),
-        };
-
-        result += match captures.name("eval_dec") {
-            None | Some("") => 0,
-            Some(value) => value.parse::<i32>().unwrap(),
-        };
-
-        result *= match captures.name("sign") {
-            None | Some("") | Some("+") => 1,
-            Some("-") => -1,
-            _ => unreachable!(),
-        };
-
-        result
-    }
-
-    fn get_time(captures: &Captures) -> u32 {
-        let mut result = 0;
+#[cfg(test)]
+mod tests {
+    use std::mem;
 
-        result +=
-        match captures.name("time") {
-            Some(value) => 1000 * value.parse::<u32>().unwrap(),
-            _ => unreachable!(),
-        };
+    use board::Board;
 
-        result +=
-        match captures.name("time_dec") {
-            None | Some("") => 0,
-            Some(value) => 10u32.pow((3 - value.len() as i32) as u32) *
-                           value.parse::<u32>().unwrap(),
-        };
+    use super::{Color, PlyPosition, Termination, TerminationReason};
 
-        result
+    #[test]
+    fn ply_positions() {
+        let position = PlyPosition::new(&Board::start().position());
+        assert_eq!(position.material, 78);
+        assert_eq!(position.piece_count, 32);
+        assert_eq!(position.halfmove_clock, 0);
+
+        // It's kept for every ply of every game
+        assert_eq!(mem::size_of::<PlyPosition>(), 16);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::CommentParser;
-    use MoveData;
 
     #[test]
-    fn comment_parsing() {
-       let comment_parser =  CommentParser::new();
-
-       assert_eq!(comment_parser.parse("-1.91/13 0.031s"), Ok(MoveData{ eval: -191, time: 31 }));
-       assert_eq!(comment_parser.parse("+0.18/15 0.45s"), Ok(MoveData{ eval: 18, time: 450 }));
-       assert_eq!(comment_parser.parse("+M17/21 0.020s"), Ok(MoveData{ eval: 10000, time: 20 }));
-       assert_eq!(comment_parser.parse("-M26/18 0.022s"), Ok(MoveData{ eval: -10000, time: 22 }));
+    fn termination_parsing() {
+        let termination = Termination::from_comment("+M1/2 0.01s, White mates").unwrap();
+        assert_eq!(termination.reason, TerminationReason::Checkmate);
+        assert_eq!(termination.winner, Some(Color::White));
+        assert_eq!(termination.text, "White mates");
+
+        let termination = Termination::from_comment("Black loses on time").unwrap();
+        assert_eq!(termination.reason, TerminationReason::Timeout);
+        assert_eq!(termination.winner, Some(Color::White));
+
+        let termination = Termination::from_comment("White resigns").unwrap();
+        assert_eq!(termination.reason, TerminationReason::Resignation);
+        assert_eq!(termination.winner, Some(Color::Black));
+
+        let termination = Termination::from_comment("White wins by adjudication: SyzygyTB").unwrap();
+        assert_eq!(termination.reason, TerminationReason::Adjudication);
+        assert_eq!(termination.winner, Some(Color::White));
+
+        let termination = Termination::from_comment("-0.01/30 1.2s, Draw by 3-fold repetition").unwrap();
+        assert_eq!(termination.reason, TerminationReason::Repetition);
+        assert_eq!(termination.winner, None);
+
+        assert_eq!(Termination::from_comment("+0.18/15 0.45s"), None);
+        assert_eq!(Termination::from_comment("White plays well"), None);
     }
 }
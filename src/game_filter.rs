@@ -0,0 +1,186 @@
+use std::fmt;
+
+use chess_pgn_parser::Game;
+use regex;
+use regex::Regex;
+
+#[derive(Debug, PartialEq)]
+pub enum GameFilterError {
+    BadTagFilter(String),
+    BadRegex(String),
+    BadDate(String),
+}
+
+impl fmt::Display for GameFilterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GameFilterError::BadTagFilter(ref filter) =>
+                write!(f, "Bad tag filter '{}', expected <key>=<regex>", filter),
+            GameFilterError::BadRegex(ref regex) =>
+                write!(f, "Bad regular expression '{}'", regex),
+            GameFilterError::BadDate(ref date) =>
+                write!(f, "Bad date '{}', expected YYYY.MM.DD", date),
+        }
+    }
+}
+
+#[derive(Clone)]
+struct TagFilter {
+    key: String,
+    regex: Regex,
+}
+
+// Selects games by the values of their PGN header tags. A game is
+// selected when it matches every filter, so an empty filter selects
+// every game.
+#[derive(Clone, Default)]
+pub struct GameFilter {
+    tags: Vec<TagFilter>,
+    date_from: Option<(u32, u32, u32)>,
+}
+
+impl GameFilter {
+    pub fn new() -> GameFilter {
+        GameFilter {
+            tags: Vec::new(),
+            date_from: None,
+        }
+    }
+
+    // Selects games where the tag `key` is exactly `value`
+    pub fn with_tag_value(mut self, key: &str, value: &str) -> GameFilter {
+        let regex = Regex::new(&format!("^{}$", regex::quote(value))).unwrap();
+
+        self.tags.push(TagFilter {
+            key: key.to_string(),
+            regex: regex,
+        });
+        self
+    }
+
+    // Selects games where the tag `key` contains a match for `regex`
+    pub fn with_tag_regex(mut self, key: &str, regex: &str)
+        -> Result<GameFilter, GameFilterError> {
+
+        let regex = Regex::new(regex)
+            .map_err(|_| GameFilterError::BadRegex(regex.to_string()))?;
+
+        self.tags.push(TagFilter {
+            key: key.to_string(),
+            regex: regex,
+        });
+        Ok(self)
+    }
+
+    // Adds a filter given as `KEY=REGEX`
+    pub fn with_tag_filter(self, filter: &str) -> Result<GameFilter, GameFilterError> {
+        let args: Vec<&str> = filter.splitn(2, '=').collect();
+        if args.len() != 2 || args[0].is_empty() {
+            return Err(GameFilterError::BadTagFilter(filter.to_string()));
+        }
+
+        self.with_tag_regex(args[0], args[1])
+    }
+
+    // Selects games played on or after `date`, given as YYYY.MM.DD.
+    // Games without a full date aren't selected.
+    pub fn with_date_from(mut self, date: &str) -> Result<GameFilter, GameFilterError> {
+        match parse_date(date) {
+            Some(date_from) => {
+                self.date_from = Some(date_from);
+                Ok(self)
+            },
+            None => Err(GameFilterError::BadDate(date.to_string())),
+        }
+    }
+
+    pub fn matches(&self, game: &Game) -> bool {
+        let tag_value = |key: &str| {
+            game.tags.iter()
+                .find(|tag| tag.0 == key)
+                .map(|tag| &tag.1[..])
+        };
+
+        for filter in &self.tags {
+            match tag_value(&filter.key) {
+                Some(value) if filter.regex.is_match(value) => { },
+                _ => { return false; }
+            }
+        }
+
+        if let Some(date_from) = self.date_from {
+            match tag_value("Date").and_then(parse_date) {
+                Some(date) if date >= date_from => { },
+                _ => { return false; }
+            }
+        }
+
+        true
+    }
+}
+
+// Parses a PGN date like `2016.03.21` into (year, month, day). Dashes
+// are allowed as separators too. Returns None for dates with unknown
+// parts like `2016.??.??`.
+fn parse_date(date: &str) -> Option<(u32, u32, u32)> {
+    let parts: Vec<&str> = date.trim().split(&['.', '-'][..]).collect();
+    if parts.len() != 3 {
+        return None;
+    }
+
+    match (parts[0].parse(), parts[1].parse(), parts[2].parse()) {
+        (Ok(year), Ok(month), Ok(day)) => Some((year, month, day)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chess_pgn_parser::read_games;
+
+    use super::{GameFilter, GameFilterError, parse_date};
+
+    #[test]
+    fn game_filtering() {
+        let games = read_games(concat!(
+            "[White \"stockfish1\"]\n[Black \"stockfish2\"]\n",
+            "[Date \"2016.03.21\"]\n[TimeControl \"9.63+0.03\"]\n[Result \"1-0\"]\n\n",
+            "1. e4 1-0\n\n",
+            "[White \"stockfish2\"]\n[Black \"stockfish1\"]\n",
+            "[Date \"2016.??.??\"]\n[TimeControl \"40/60\"]\n[Result \"0-1\"]\n\n",
+            "1. e4 0-1\n\n")).unwrap();
+
+        let selected = |filter: GameFilter| {
+            games.iter().map(|game| filter.matches(game)).collect::<Vec<_>>()
+        };
+
+        assert_eq!(selected(GameFilter::new()), [true, true]);
+        assert_eq!(selected(GameFilter::new().with_tag_value("White", "stockfish1")),
+                   [true, false]);
+        assert_eq!(selected(GameFilter::new().with_tag_value("White", "stockfish")),
+                   [false, false]);
+        assert_eq!(selected(GameFilter::new().with_tag_value("TimeControl", "9.63+0.03")),
+                   [true, false]);
+        assert_eq!(selected(GameFilter::new().with_tag_filter("Black=fish[12]$").unwrap()),
+                   [true, true]);
+        assert_eq!(selected(GameFilter::new().with_tag_filter("Event=.*").unwrap()),
+                   [false, false]);
+        assert_eq!(selected(GameFilter::new().with_date_from("2016-01-01").unwrap()),
+                   [true, false]);
+        assert_eq!(selected(GameFilter::new().with_date_from("2016.03.22").unwrap()),
+                   [false, false]);
+    }
+
+    #[test]
+    fn game_filter_errors() {
+        assert_eq!(GameFilter::new().with_tag_filter("White").err(),
+                   Some(GameFilterError::BadTagFilter("White".to_string())));
+        assert_eq!(GameFilter::new().with_tag_filter("White=(").err(),
+                   Some(GameFilterError::BadRegex("(".to_string())));
+        assert_eq!(GameFilter::new().with_date_from("March").err(),
+                   Some(GameFilterError::BadDate("March".to_string())));
+
+        assert_eq!(parse_date("2016.03.21"), Some((2016, 3, 21)));
+        assert_eq!(parse_date("2016.03"), None);
+    }
+}
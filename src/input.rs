@@ -0,0 +1,217 @@
+use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+use clap::ArgMatches;
+
+use cara::{GameData, GameError, GameFilter, GameReader, ReadError};
+
+// The PGN files given on the command line, with directories
+// and glob patterns expanded
+pub fn pgn_files(matches: &ArgMatches) -> Vec<PathBuf> {
+
+    let mut inputs = matches.values_of("file").unwrap();
+    if matches.is_present("resign_option") {
+        // With the rules given as options every positional is a file
+        inputs.extend(matches.value_of("resign_rule"));
+        inputs.extend(matches.value_of("draw_rule"));
+    }
+    if let Some(more_inputs) = matches.values_of("more_files") {
+        inputs.extend(more_inputs);
+    }
+
+    match cara::find_pgn_files(&inputs) {
+        Ok(paths) => paths,
+        Err(error) => {
+            println_stderr!("error: {}", error);
+            exit(1);
+        }
+    }
+}
+
+// Reads the games in `paths` one at a time. All the files are
+// opened up front so that a missing one is reported before any
+// output.
+pub fn stream_games(matches: &ArgMatches, paths: Vec<PathBuf>) -> GameStream {
+
+    let name_files = paths.len() > 1;
+    let comment_format = matches.value_of("comment_format").unwrap_or("auto");
+    let filter = game_filter(matches);
+
+    let readers: Vec<_> = paths.into_iter()
+        .map(|path| match cara::open_games(&path) {
+            Ok(games) => {
+                let games = games
+                    .with_comment_format(cara::comment_format_by_name(comment_format).unwrap())
+                    .with_filter(filter.clone());
+                (path, games)
+            },
+            Err(error) => exit_on_read_error(&path, name_files, error),
+        })
+        .collect();
+
+    GameStream {
+        readers: readers,
+        file_index: 0,
+        name_files: name_files,
+        skip_invalid: matches.is_present("skip_invalid"),
+        skipped: Vec::new(),
+    }
+}
+
+// The filter for the games selected by the tag options
+fn game_filter(matches: &ArgMatches) -> GameFilter {
+    let mut filter = GameFilter::new();
+
+    for &(name, key) in [("white", "White"),
+                         ("black", "Black"),
+                         ("event", "Event"),
+                         ("time_control", "TimeControl")].iter() {
+        if let Some(value) = matches.value_of(name) {
+            filter = filter.with_tag_value(key, value);
+        }
+    }
+
+    let mut result = Ok(filter);
+
+    if let Some(date) = matches.value_of("date_from") {
+        result = result.and_then(|filter| filter.with_date_from(date));
+    }
+
+    for tag in matches.values_of("tag").unwrap_or_default() {
+        result = result.and_then(|filter| filter.with_tag_filter(tag));
+    }
+
+    match result {
+        Ok(filter) => filter,
+        Err(error) => {
+            println_stderr!("error: {}", error);
+            exit(1);
+        }
+    }
+}
+
+// Errors are only prefixed with the file's name when there's
+// more than one file
+fn exit_on_read_error(path: &Path, name_file: bool, error: ReadError) -> ! {
+    if name_file {
+        println_stderr!("error: {}: {}", path.display(), error);
+    } else {
+        println_stderr!("error: {}", error);
+    }
+    exit(1);
+}
+
+// A game that was dropped with --skip-invalid
+struct SkippedGame {
+    file_index: usize,
+    game_number: u32,
+    reason: &'static str,
+}
+
+// The reasons games are skipped for, in the order they're reported
+const SKIP_REASONS: [&str; 6] = [
+    "Can't parse pgn",
+    "Unknown result",
+    "Bad FEN",
+    "Illegal move",
+    "Missing comment",
+    "Bad comment format",
+];
+
+fn skip_reason(error: &ReadError) -> Option<&'static str> {
+    match *error {
+        ReadError::CantParsePgn => Some(SKIP_REASONS[0]),
+        ReadError::BadGame(ref error) => match error.error {
+            GameError::UnknownGameTermination => Some(SKIP_REASONS[1]),
+            GameError::BadFen => Some(SKIP_REASONS[2]),
+            GameError::IllegalMove { .. } => Some(SKIP_REASONS[3]),
+            GameError::MissingComment { .. } => Some(SKIP_REASONS[4]),
+            GameError::BadComment { .. } => Some(SKIP_REASONS[5]),
+        },
+        _ => None,
+    }
+}
+
+// Yields the games in every file along with the index of the file
+// each game came from and the game's number in that file. Invalid
+// games end the run unless they're being skipped, in which case
+// they're remembered for the summary that `print_skipped` shows.
+pub struct GameStream {
+    readers: Vec<(PathBuf, GameReader<Box<dyn BufRead>>)>,
+    file_index: usize,
+    name_files: bool,
+    skip_invalid: bool,
+    skipped: Vec<SkippedGame>,
+}
+
+impl Iterator for GameStream {
+    type Item = (usize, u32, GameData);
+
+    fn next(&mut self) -> Option<(usize, u32, GameData)> {
+        while self.file_index < self.readers.len() {
+            let reader = &mut self.readers[self.file_index].1;
+            let result = reader.next();
+            let game_number = reader.games_read();
+
+            let error = match result {
+                Some(Ok(game_data)) => {
+                    return Some((self.file_index, game_number, game_data));
+                },
+                Some(Err(error)) => error,
+                None => {
+                    self.file_index += 1;
+                    continue;
+                }
+            };
+
+            match skip_reason(&error) {
+                Some(reason) if self.skip_invalid => {
+                    self.skipped.push(SkippedGame {
+                        file_index: self.file_index,
+                        game_number: game_number,
+                        reason: reason,
+                    });
+                },
+                _ => exit_on_read_error(&self.readers[self.file_index].0, self.name_files, error),
+            }
+        }
+
+        None
+    }
+}
+
+impl GameStream {
+    // Prints how many games were skipped for each reason, and which
+    // ones, to stderr
+    pub fn print_skipped(&self) {
+        if self.skipped.is_empty() {
+            return;
+        }
+
+        println_stderr!("Skipped {} invalid games", self.skipped.len());
+
+        for reason in SKIP_REASONS.iter() {
+            let games: Vec<String> = self.skipped.iter()
+                .filter(|game| game.reason == *reason)
+                .map(|game| {
+                    if self.name_files {
+                        format!("{}:{}",
+                                self.readers[game.file_index].0.display(),
+                                game.game_number)
+                    } else {
+                        game.game_number.to_string()
+                    }
+                })
+                .collect();
+
+            if !games.is_empty() {
+                println_stderr!("  {}: {} ({} {})",
+                                reason,
+                                games.len(),
+                                if games.len() == 1 { "game" } else { "games" },
+                                games.join(", "));
+            }
+        }
+    }
+}
@@ -0,0 +1,27 @@
+extern crate bzip2;
+extern crate chess_pgn_parser;
+extern crate flate2;
+extern crate glob;
+extern crate regex;
+extern crate xz2;
+extern crate zstd;
+
+mod adjudication;
+mod board;
+mod comment_format;
+mod game_data;
+mod game_filter;
+mod reader;
+mod wdl;
+
+pub use adjudication::{AdjudicationOutcome, DrawClaim, DrawRule, DrawRuleParsingError,
+                       GameStats, ResignRule, ResignRuleParsingError, RulePriority, RuleType,
+                       TablebaseRule, adjudicate_game, adjudicate_game_with, find_draw_claim};
+pub use board::{Board, BoardError, Position};
+pub use comment_format::{AutoFormat, CommentError, CommentFormat, CutechessFormat,
+                         comment_format_by_name};
+pub use game_data::{Color, Eval, GameData, GameError, GameMappingError, MoveData, PlyPosition,
+                    Termination, TerminationReason, map_game_data};
+pub use game_filter::{GameFilter, GameFilterError};
+pub use reader::{GameReader, ReadError, find_pgn_files, open_games, read_games};
+pub use wdl::{Wdl, WdlModel};
@@ -1,6 +1,5 @@
-extern crate chess_pgn_parser;
+extern crate cara;
 extern crate clap;
-extern crate regex;
 
 // http://stackoverflow.com/a/27590832
 #[macro_use]
@@ -17,143 +16,324 @@ mod macros {
     );
 }
 
-mod game_data;
+mod input;
+mod recommend;
 mod rule_test;
 
-use std::fs::File;
-use std::io::{Read, Write};
-use std::process::exit;
+use clap::{App, Arg, SubCommand};
 
-use clap::{App, Arg, ArgMatches, SubCommand};
-use chess_pgn_parser::Game;
-
-use game_data::{GameMappingError, GameError, map_game_data};
+use recommend::{draw_main, frontier_main, optimize_main, resign_main};
 use rule_test::main as test_rule_main;
 
-// The evaluation, in centipawns, of the engine after the move
-// and the time taken in milliseconds
-#[derive(Debug, PartialEq)]
-pub struct MoveData {
-    eval: i32,
-    time: u32,
-}
-
-pub struct GameData {
-    // We store the score as 10x the usual values and
-    // scale down when displaying results.
-    // 1-0     => 10
-    // 1/2-1/2 => 5
-    // 0-1     => 0
-    pub score10: u32,
-    pub move_data: Vec<MoveData>,
-}
-
 fn build_app<'a, 'v, 'ab, 'u, 'h, 'ar>() -> App<'a, 'v, 'ab, 'u, 'h, 'ar> {
 
     App::new("Chess-Adjudication-Rule-Analyzer")
         .version("0.1")
         .author("Henri Wiechers <henri@wiechers.me>")
         .about("Tool for studying chess adjudication rules")
-        .subcommand(SubCommand::with_name("resign")
+        .subcommand(input_args(SubCommand::with_name("resign")
                     .about("Recommends a resign rule")
                     .arg(Arg::with_name("file")
-                             .help("The PGN file to analyze")
+                             .help("The PGN files, directories or glob patterns to analyze")
                              .index(1)
-                             .required(true)))
-        .subcommand(SubCommand::with_name("draw")
+                             .required(true)
+                             .multiple(true))
+                    .arg(Arg::with_name("evals")
+                             .long("evals")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The evaluations to try (default 100:1000:50)"))
+                    .arg(Arg::with_name("counts")
+                             .long("counts")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The move counts to try (default 1:10:1)"))
+                    .arg(Arg::with_name("max_wrong")
+                             .long("max-wrong")
+                             .takes_value(true)
+                             .value_name("count")
+                             .help("The most wrongly adjudicated games allowed (default 0)"))
+                    .arg(Arg::with_name("top")
+                             .long("top")
+                             .takes_value(true)
+                             .value_name("count")
+                             .help("The number of rules to show (default 10)"))))
+        .subcommand(input_args(SubCommand::with_name("draw")
                     .about("Recommends a draw rule")
                     .arg(Arg::with_name("file")
-                             .help("The PGN file to analyze")
+                             .help("The PGN files, directories or glob patterns to analyze")
                              .index(1)
-                             .required(true)))
-        .subcommand(SubCommand::with_name("test")
+                             .required(true)
+                             .multiple(true))
+                    .arg(Arg::with_name("from_moves")
+                             .long("from-moves")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The move numbers to try (default 20:80:10)"))
+                    .arg(Arg::with_name("evals")
+                             .long("evals")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The evaluations to try (default 10:50:10)"))
+                    .arg(Arg::with_name("counts")
+                             .long("counts")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The move counts to try (default 1:10:1)"))
+                    .arg(Arg::with_name("max_wrong")
+                             .long("max-wrong")
+                             .takes_value(true)
+                             .value_name("count")
+                             .help("The most wrongly adjudicated games allowed (default 0)"))
+                    .arg(Arg::with_name("top")
+                             .long("top")
+                             .takes_value(true)
+                             .value_name("count")
+                             .help("The number of rules to show (default 10)"))))
+        .subcommand(input_args(SubCommand::with_name("optimize")
+                    .about("Recommends a resign rule and draw rule to use together")
+                    .arg(Arg::with_name("file")
+                             .help("The PGN files, directories or glob patterns to analyze")
+                             .index(1)
+                             .required(true)
+                             .multiple(true))
+                    .arg(Arg::with_name("resign_evals")
+                             .long("resign-evals")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The resign evaluations to try (default 200:1000:100)"))
+                    .arg(Arg::with_name("resign_counts")
+                             .long("resign-counts")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The resign move counts to try (default 2:8:2)"))
+                    .arg(Arg::with_name("draw_from_moves")
+                             .long("draw-from-moves")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The draw move numbers to try (default 20:80:20)"))
+                    .arg(Arg::with_name("draw_evals")
+                             .long("draw-evals")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The draw evaluations to try (default 10:50:20)"))
+                    .arg(Arg::with_name("draw_counts")
+                             .long("draw-counts")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The draw move counts to try (default 4:12:4)"))
+                    .arg(Arg::with_name("objective")
+                             .long("objective")
+                             .takes_value(true)
+                             .possible_value("time")
+                             .possible_value("mse")
+                             .help("Rank rule pairs by most time saved or lowest MSE (default time)"))
+                    .arg(Arg::with_name("max_wrong")
+                             .long("max-wrong")
+                             .takes_value(true)
+                             .value_name("count")
+                             .help("The most wrongly adjudicated games allowed \
+                                    (default 0 for time, unlimited for mse)"))
+                    .arg(Arg::with_name("min_time_saved")
+                             .long("min-time-saved")
+                             .takes_value(true)
+                             .value_name("percentage")
+                             .help("The least time that must be saved (default 0)"))
+                    .arg(Arg::with_name("top")
+                             .long("top")
+                             .takes_value(true)
+                             .value_name("count")
+                             .help("The number of rule pairs to show (default 10)"))))
+        .subcommand(input_args(SubCommand::with_name("frontier")
+                    .about("Lists the resign and draw rule pairs with the best \
+                            trade-offs between time saved and error")
+                    .arg(Arg::with_name("file")
+                             .help("The PGN files, directories or glob patterns to analyze")
+                             .index(1)
+                             .required(true)
+                             .multiple(true))
+                    .arg(Arg::with_name("resign_evals")
+                             .long("resign-evals")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The resign evaluations to try (default 200:1000:100)"))
+                    .arg(Arg::with_name("resign_counts")
+                             .long("resign-counts")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The resign move counts to try (default 2:8:2)"))
+                    .arg(Arg::with_name("draw_from_moves")
+                             .long("draw-from-moves")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The draw move numbers to try (default 20:80:20)"))
+                    .arg(Arg::with_name("draw_evals")
+                             .long("draw-evals")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The draw evaluations to try (default 10:50:20)"))
+                    .arg(Arg::with_name("draw_counts")
+                             .long("draw-counts")
+                             .takes_value(true)
+                             .value_name("start:end:step")
+                             .help("The draw move counts to try (default 4:12:4)"))
+                    .arg(Arg::with_name("error")
+                             .long("error")
+                             .takes_value(true)
+                             .possible_value("wrong")
+                             .possible_value("mse")
+                             .help("Measure error by wrong adjudications or MSE (default wrong)"))
+                    .arg(Arg::with_name("format")
+                             .long("format")
+                             .takes_value(true)
+                             .possible_value("table")
+                             .possible_value("csv")
+                             .help("The output format (default table)"))))
+        .subcommand(input_args(SubCommand::with_name("test")
                     .about("Applies <resign_rule> and <draw_rule> on <file>")
                     .arg(Arg::with_name("file")
-                             .help("The PGN file to analyze")
+                             .help("The PGN file, directory or glob pattern to analyze")
                              .index(1)
                              .required(true))
                     .arg(Arg::with_name("resign_rule")
-                             .help("The resign rule in format <eval>/<count> or 'none'")
-                             .index(2)
-                             .required(true))
+                             .help("The resign rule in format <eval>/<count>, M<moves>/<count>, \
+                                    W<win_percent>/<count>, L<loss_percent>/<count> or 'none', \
+                                    optionally followed by ',depth=<min_depth>', ',twosided' \
+                                    and ',wdl=ply|material'")
+                             .index(2))
                     .arg(Arg::with_name("draw_rule")
-                             .help("The draw rule in format <move_number>:<eval>/<count> or 'none'")
-                             .index(3)
-                             .required(true))
+                             .help("The draw rule in format <move_number>:<eval>/<count>, \
+                                    <move_number>:D<draw_percent>/<count> or 'none', optionally \
+                                    followed by ',depth=<min_depth>' and ',wdl=ply|material'")
+                             .index(3))
+                    .arg(Arg::with_name("more_files")
+                             .help("More PGN files, directories or glob patterns to analyze")
+                             .index(4)
+                             .multiple(true))
+                    .arg(Arg::with_name("resign_option")
+                             .long("resign")
+                             .takes_value(true)
+                             .value_name("rule")
+                             .requires("draw_option")
+                             .help("Gives <resign_rule> as an option, so that all the \
+                                    positional args are files"))
+                    .arg(Arg::with_name("draw_option")
+                             .long("draw")
+                             .takes_value(true)
+                             .value_name("rule")
+                             .requires("resign_option")
+                             .help("Gives <draw_rule> as an option, so that all the \
+                                    positional args are files"))
                     .arg(Arg::with_name("verbose")
                               .long("verbose")
                               .help("Turns on verbose output"))
-                              )
+                    .arg(Arg::with_name("per_file")
+                              .long("per-file")
+                              .help("Also shows the results for each file"))
+                    .arg(Arg::with_name("per_engine")
+                              .long("per-engine")
+                              .help("Also shows the results for each engine and color"))
+                    .arg(Arg::with_name("tablebase")
+                              .long("tablebase")
+                              .takes_value(true)
+                              .value_name("pieces")
+                              .help("Also ends games once at most <pieces> pieces are left"))
+                    .arg(Arg::with_name("priority")
+                              .long("priority")
+                              .takes_value(true)
+                              .possible_value("resign")
+                              .possible_value("draw")
+                              .help("The rule applied when the resign and draw rules both apply \
+                                     on the same ply (default draw)"))
+                    .arg(Arg::with_name("draw_claims")
+                              .long("draw-claims")
+                              .help("Also shows the games that went on after a fifty move, \
+                                     repetition or insufficient material draw"))
+                    .arg(Arg::with_name("tablebase_savings")
+                              .long("tablebase-savings")
+                              .help("Also shows the time that ending games with 3 to 7 \
+                                     pieces left would save"))
+                    .arg(Arg::with_name("format")
+                              .long("format")
+                              .takes_value(true)
+                              .possible_value("text")
+                              .possible_value("json")
+                              .help("The output format (default text)"))
+                              ))
         .subcommand_required_else_help(true)
 }
 
+// Adds the arguments for how games are read, which every
+// subcommand takes
+fn input_args<'a, 'v, 'ab, 'u, 'h, 'ar>(subcommand: App<'a, 'v, 'ab, 'u, 'h, 'ar>)
+    -> App<'a, 'v, 'ab, 'u, 'h, 'ar> {
+
+    subcommand
+        .arg(Arg::with_name("comment_format")
+                 .long("comment-format")
+                 .takes_value(true)
+                 .possible_value("auto")
+                 .possible_value("cutechess")
+                 .possible_value("lichess")
+                 .help("The format of the move comments (default auto)"))
+        .arg(Arg::with_name("skip_invalid")
+                 .long("skip-invalid")
+                 .help("Skips games that can't be read instead of stopping"))
+        .arg(Arg::with_name("white")
+                 .long("white")
+                 .takes_value(true)
+                 .value_name("player")
+                 .help("Only uses games where <player> is White"))
+        .arg(Arg::with_name("black")
+                 .long("black")
+                 .takes_value(true)
+                 .value_name("player")
+                 .help("Only uses games where <player> is Black"))
+        .arg(Arg::with_name("event")
+                 .long("event")
+                 .takes_value(true)
+                 .value_name("event")
+                 .help("Only uses games from <event>"))
+        .arg(Arg::with_name("time_control")
+                 .long("time-control")
+                 .takes_value(true)
+                 .value_name("time_control")
+                 .help("Only uses games with the TimeControl tag <time_control>"))
+        .arg(Arg::with_name("date_from")
+                 .long("date-from")
+                 .takes_value(true)
+                 .value_name("YYYY.MM.DD")
+                 .help("Only uses games played on or after the date"))
+        .arg(Arg::with_name("tag")
+                 .long("tag")
+                 .takes_value(true)
+                 .multiple(true)
+                 .number_of_values(1)
+                 .value_name("key=regex")
+                 .help("Only uses games where the tag <key> matches <regex>"))
+}
 
 fn main() {
 
     let matches = build_app().get_matches();
 
-    if let Some(_) = matches.subcommand_matches("resign") {
-        println_stderr!("This command isn't implemented yet! :O");
-        exit(1);
+    if let Some(ref matches) = matches.subcommand_matches("resign") {
+        resign_main(matches);
     }
 
-    if let Some(_) = matches.subcommand_matches("draw") {
-        println_stderr!("This command isn't implemented yet! :O");
-        exit(1);
+    if let Some(ref matches) = matches.subcommand_matches("draw") {
+        draw_main(matches);
     }
 
-    if let Some(ref matches) = matches.subcommand_matches("test") {
-        test_rule_main(matches);
+    if let Some(ref matches) = matches.subcommand_matches("optimize") {
+        optimize_main(matches);
     }
-}
 
-fn read_games(matches: &ArgMatches) ->  Vec<GameData> {
-
-    let path = matches.value_of("file").unwrap();
-
-    let mut pgn_file = match File::open(path) {
-        Ok(file) => file,
-        Err(_) => {
-            println_stderr!("error: Can't open file");
-            exit(1);
-        }
-    };
-
-    let mut pgn = String::new();
-    match pgn_file.read_to_string(&mut pgn) {
-        Ok(_) => { },
-        Err(_) => {
-            println_stderr!("error: Can't read file");
-            exit(1);
-        }
+    if let Some(ref matches) = matches.subcommand_matches("frontier") {
+        frontier_main(matches);
     }
 
-    let games: Vec<Game> = match chess_pgn_parser::read_games(&pgn) {
-        Ok(games) => games,
-        Err(_) => {
-            println_stderr!("error: Can't parse pgn file");
-            exit(1);
-        }
-    };
-
-    return match map_game_data(&games) {
-        Ok(game_data) => game_data,
-        Err(GameMappingError { game_number, error }) => {
-            match error {
-                GameError::UnknownGameTermination => {
-                    println_stderr!("error: Game {} has unknown result",
-                                    game_number);
-                },
-                GameError::MissingComment{ply} => {
-                    println_stderr!("error: Game {}, Ply {} - Missing comment",
-                                    game_number, ply);
-                }
-                GameError::BadComment{ply} => {
-                    println_stderr!("error: Game {}, Ply {} - Bad comment format",
-                                    game_number, ply);
-                }
-            }
-            exit(1);
-        }
-    };
+    if let Some(ref matches) = matches.subcommand_matches("test") {
+        test_rule_main(matches);
+    }
 }
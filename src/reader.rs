@@ -0,0 +1,454 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use bzip2::bufread::MultiBzDecoder;
+use chess_pgn_parser;
+use chess_pgn_parser::Game;
+use flate2::bufread::MultiGzDecoder;
+use glob;
+use xz2::bufread::XzDecoder;
+use zstd;
+
+use comment_format::{AutoFormat, CommentFormat};
+use game_filter::GameFilter;
+use game_data::{GameData, GameMappingError, map_single_game_data};
+
+#[derive(Debug)]
+pub enum ReadError {
+    CantOpenFile(io::Error),
+    CantReadFile(io::Error),
+    CantParsePgn,
+    BadGame(GameMappingError),
+    CantReadDirectory(io::Error),
+    BadPattern(String),
+    NoPgnFiles(String),
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReadError::CantOpenFile(_) => write!(f, "Can't open file"),
+            ReadError::CantReadFile(_) => write!(f, "Can't read file"),
+            ReadError::CantParsePgn => write!(f, "Can't parse pgn file"),
+            ReadError::BadGame(ref error) => write!(f, "{}", error),
+            ReadError::CantReadDirectory(_) => write!(f, "Can't read directory"),
+            ReadError::BadPattern(ref pattern) => write!(f, "Bad file pattern '{}'", pattern),
+            ReadError::NoPgnFiles(ref input) => write!(f, "No PGN files found for '{}'", input),
+        }
+    }
+}
+
+impl Error for ReadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            ReadError::CantOpenFile(ref error) |
+            ReadError::CantReadFile(ref error) |
+            ReadError::CantReadDirectory(ref error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+// Reads games from PGN input one at a time so that only a single
+// game's text is held in memory. The input is split into games on
+// the first tag line after a game's movetext.
+pub struct GameReader<R> {
+    input: R,
+    comment_format: Box<dyn CommentFormat>,
+    filter: GameFilter,
+    games_read: u32,
+    // The line that started the next game, if it's been read
+    next_line: Option<String>,
+    // Games in the last chunk that haven't been returned yet
+    pending: Vec<Game>,
+}
+
+impl<R: BufRead> GameReader<R> {
+    pub fn new(input: R) -> GameReader<R> {
+        GameReader {
+            input: input,
+            comment_format: Box::new(AutoFormat::new()),
+            filter: GameFilter::new(),
+            games_read: 0,
+            next_line: None,
+            pending: Vec::new(),
+        }
+    }
+
+    // Reads the engine data from move comments in `comment_format`
+    // instead of detecting the format
+    pub fn with_comment_format(mut self, comment_format: Box<dyn CommentFormat>)
+        -> GameReader<R> {
+
+        self.comment_format = comment_format;
+        self
+    }
+
+    // Only returns the games that `filter` selects. The games that
+    // aren't selected still count towards game numbers.
+    pub fn with_filter(mut self, filter: GameFilter) -> GameReader<R> {
+        self.filter = filter;
+        self
+    }
+
+    // The number of games read so far, counting the ones that were
+    // filtered out or couldn't be read. It's the number of the last
+    // game returned.
+    pub fn games_read(&self) -> u32 {
+        self.games_read
+    }
+
+    // Returns the text of the next game or None at the end of the input
+    fn read_chunk(&mut self) -> Result<Option<String>, ReadError> {
+        let mut chunk = self.next_line.take().unwrap_or_default();
+        let mut in_movetext = false;
+        let mut in_comment = false;
+
+        loop {
+            let mut line = String::new();
+            match self.input.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => { },
+                Err(error) => { return Err(ReadError::CantReadFile(error)); }
+            }
+
+            let trimmed = line.trim();
+            if !in_comment && trimmed.starts_with('[') {
+                if in_movetext {
+                    self.next_line = Some(line);
+                    break;
+                }
+            } else if !trimmed.is_empty() {
+                in_movetext = true;
+            }
+
+            in_comment = ends_in_comment(&line, in_comment);
+            chunk.push_str(&line);
+        }
+
+        if chunk.trim().is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(chunk))
+        }
+    }
+
+    fn next_game(&mut self) -> Result<Option<GameData>, ReadError> {
+        let game = loop {
+            while self.pending.is_empty() {
+                let chunk = match self.read_chunk()? {
+                    Some(chunk) => chunk,
+                    None => { return Ok(None); }
+                };
+
+                match chess_pgn_parser::read_games(&chunk) {
+                    Ok(mut games) => {
+                        games.reverse();
+                        self.pending = games;
+                    },
+                    Err(_) => {
+                        // Chunks nearly always hold a single game, so
+                        // this keeps later game numbers right
+                        self.games_read += 1;
+                        return Err(ReadError::CantParsePgn);
+                    }
+                }
+            }
+
+            let game = self.pending.pop().unwrap();
+            self.games_read += 1;
+
+            if self.filter.matches(&game) {
+                break game;
+            }
+        };
+
+        match map_single_game_data(&game, &*self.comment_format) {
+            Ok(game_data) => Ok(Some(game_data)),
+            Err(error) => Err(ReadError::BadGame(GameMappingError {
+                game_number: self.games_read,
+                error: error,
+            })),
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for GameReader<R> {
+    type Item = Result<GameData, ReadError>;
+
+    fn next(&mut self) -> Option<Result<GameData, ReadError>> {
+        match self.next_game() {
+            Ok(Some(game_data)) => Some(Ok(game_data)),
+            Ok(None) => None,
+            Err(error) => Some(Err(error)),
+        }
+    }
+}
+
+// Returns whether a `{` comment is still open at the end of `line`
+fn ends_in_comment(line: &str, in_comment: bool) -> bool {
+    let mut result = in_comment;
+
+    for c in line.chars() {
+        match c {
+            '{' if !result => { result = true; },
+            '}' if result => { result = false; },
+            ';' if !result => { break; },
+            _ => { },
+        }
+    }
+
+    result
+}
+
+// The ways that PGN files may be compressed
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compression {
+    None,
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Compression {
+    // Detects the compression from the first bytes of a file and
+    // falls back on its extension when they aren't recognised
+    fn detect(header: &[u8], path: &Path) -> Compression {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            return Compression::Gzip;
+        }
+
+        if header.starts_with(b"BZh") {
+            return Compression::Bzip2;
+        }
+
+        if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            return Compression::Xz;
+        }
+
+        if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            return Compression::Zstd;
+        }
+
+        let extension = path.extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+
+        match extension.as_ref().map(|extension| &extension[..]) {
+            Some("gz") => Compression::Gzip,
+            Some("bz2") => Compression::Bzip2,
+            Some("xz") => Compression::Xz,
+            Some("zst") => Compression::Zstd,
+            _ => Compression::None,
+        }
+    }
+}
+
+// Wraps `input` so that it's decompressed as it's read
+fn decompress<R: BufRead + 'static>(input: R, compression: Compression)
+    -> io::Result<Box<dyn BufRead>> {
+
+    Ok(match compression {
+        Compression::None => Box::new(input),
+        Compression::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(input))),
+        Compression::Bzip2 => Box::new(BufReader::new(MultiBzDecoder::new(input))),
+        Compression::Xz => Box::new(BufReader::new(XzDecoder::new_multi_decoder(input))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(input)?)),
+    })
+}
+
+// Opens the PGN file at `path` for reading one game at a time.
+// Files compressed with gzip, bzip2, xz or zstd are decompressed
+// as they're read.
+pub fn open_games<P: AsRef<Path>>(path: P)
+    -> Result<GameReader<Box<dyn BufRead>>, ReadError> {
+
+    let path = path.as_ref();
+
+    let mut input = match File::open(path) {
+        Ok(file) => BufReader::new(file),
+        Err(error) => { return Err(ReadError::CantOpenFile(error)); }
+    };
+
+    let compression = match input.fill_buf() {
+        Ok(header) => Compression::detect(header, path),
+        Err(error) => { return Err(ReadError::CantReadFile(error)); }
+    };
+
+    match decompress(input, compression) {
+        Ok(input) => Ok(GameReader::new(input)),
+        Err(error) => Err(ReadError::CantReadFile(error)),
+    }
+}
+
+// Reads the PGN file at `path` and maps every game in it
+pub fn read_games<P: AsRef<Path>>(path: P) -> Result<Vec<GameData>, ReadError> {
+    open_games(path)?.collect()
+}
+
+// Expands the paths given on the command line into the PGN files to
+// read. Directories are replaced by the PGN files in them, including
+// compressed ones like `.pgn.gz`, and glob patterns by the files they
+// match. Plain files are kept as they are so that a missing one is
+// reported when it's opened.
+pub fn find_pgn_files(inputs: &[&str]) -> Result<Vec<PathBuf>, ReadError> {
+    let mut result: Vec<PathBuf> = Vec::new();
+
+    for input in inputs {
+        let path = Path::new(input);
+
+        let found = if path.exists() || !is_pattern(input) {
+            expand_path(path)?
+        } else {
+            expand_pattern(input)?
+        };
+
+        if found.is_empty() {
+            return Err(ReadError::NoPgnFiles(input.to_string()));
+        }
+
+        result.extend(found);
+    }
+
+    Ok(result)
+}
+
+fn is_pattern(input: &str) -> bool {
+    input.contains(&['*', '?', '['][..])
+}
+
+fn is_pgn_file(path: &Path) -> bool {
+    let name = match path.file_name() {
+        Some(name) => name.to_string_lossy().to_lowercase(),
+        None => { return false; }
+    };
+
+    path.is_file() &&
+    [".pgn", ".pgn.gz", ".pgn.bz2", ".pgn.xz", ".pgn.zst"].iter()
+        .any(|extension| name.ends_with(extension))
+}
+
+fn expand_path(path: &Path) -> Result<Vec<PathBuf>, ReadError> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(error) => { return Err(ReadError::CantReadDirectory(error)); }
+    };
+
+    let mut result: Vec<PathBuf> = Vec::new();
+    for entry in entries {
+        match entry {
+            Ok(entry) => {
+                if is_pgn_file(&entry.path()) {
+                    result.push(entry.path());
+                }
+            },
+            Err(error) => { return Err(ReadError::CantReadDirectory(error)); }
+        }
+    }
+
+    result.sort();
+    Ok(result)
+}
+
+fn expand_pattern(pattern: &str) -> Result<Vec<PathBuf>, ReadError> {
+    let paths = match glob::glob(pattern) {
+        Ok(paths) => paths,
+        Err(_) => { return Err(ReadError::BadPattern(pattern.to_string())); }
+    };
+
+    let mut result: Vec<PathBuf> = Vec::new();
+    for path in paths {
+        match path {
+            Ok(path) => {
+                if path.is_dir() {
+                    result.extend(expand_path(&path)?);
+                } else {
+                    result.push(path);
+                }
+            },
+            Err(error) => { return Err(ReadError::CantReadDirectory(error.into())); }
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Write};
+    use std::path::Path;
+
+    use bzip2;
+    use flate2;
+    use xz2;
+    use zstd;
+
+    use super::{Compression, GameReader, decompress};
+
+    #[test]
+    fn games_are_split_on_tags_after_movetext() {
+        let pgn = concat!(
+            "[Result \"1-0\"]\n",
+            "\n",
+            "1. e4 {+0.50/10 0.1s\n",
+            "[%eval 0.5]} 1-0\n",
+            "\n",
+            "[Result \"0-1\"]\n",
+            "[PlyCount \"1\"]\n",
+            "1. e4 {+0.50/10 0.1s} 0-1\n");
+
+        let games: Vec<_> = GameReader::new(pgn.as_bytes()).collect();
+
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0].as_ref().unwrap().score10, 10);
+        assert_eq!(games[1].as_ref().unwrap().score10, 0);
+    }
+
+    #[test]
+    fn compressed_input_is_detected_and_read() {
+        let pgn = "[Result \"1-0\"]\n\n1. e4 {+0.50/10 0.1s} 1-0\n";
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(pgn.as_bytes()).unwrap();
+
+        let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        bzip2.write_all(pgn.as_bytes()).unwrap();
+
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+        xz.write_all(pgn.as_bytes()).unwrap();
+
+        let inputs = vec![
+            (pgn.as_bytes().to_vec(), Compression::None),
+            (gzip.finish().unwrap(), Compression::Gzip),
+            (bzip2.finish().unwrap(), Compression::Bzip2),
+            (xz.finish().unwrap(), Compression::Xz),
+            (zstd::encode_all(pgn.as_bytes(), 0).unwrap(), Compression::Zstd),
+        ];
+
+        for (input, compression) in inputs {
+            assert_eq!(Compression::detect(&input, Path::new("games")), compression);
+
+            let reader = decompress(Cursor::new(input), compression).unwrap();
+            let games: Vec<_> = GameReader::new(reader).collect();
+
+            assert_eq!(games.len(), 1);
+            assert_eq!(games[0].as_ref().unwrap().score10, 10);
+        }
+    }
+
+    #[test]
+    fn compression_falls_back_on_extension() {
+        assert_eq!(Compression::detect(b"", Path::new("games.pgn.gz")), Compression::Gzip);
+        assert_eq!(Compression::detect(b"", Path::new("games.pgn.zst")), Compression::Zstd);
+        assert_eq!(Compression::detect(b"[Event", Path::new("games.pgn")), Compression::None);
+    }
+}
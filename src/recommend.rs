@@ -0,0 +1,484 @@
+use std::cmp::{max, Ordering};
+use std::fmt;
+use std::fmt::Display;
+use std::io::Write;
+use std::process::exit;
+
+use clap::ArgMatches;
+
+use cara::{AdjudicationOutcome, DrawRule, GameData, ResignRule, adjudicate_game};
+
+use input::{pgn_files, stream_games};
+use rule_test::{RuleTestSummary, format_time};
+
+// A rule that was tried and how it performed
+#[derive(Clone)]
+struct Candidate<R> {
+    rule: R,
+    summary: RuleTestSummary,
+}
+
+// A resign rule and a draw rule applied together
+#[derive(Clone)]
+struct RulePair {
+    resign: ResignRule,
+    draw: DrawRule,
+}
+
+impl Display for RulePair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}", self.resign, self.draw)
+    }
+}
+
+// What `optimize` ranks the rule pairs by
+enum Objective {
+    TimeSaved,
+    Mse,
+}
+
+// How `frontier` measures the error of a rule pair
+enum ErrorMeasure {
+    Wrong,
+    Mse,
+}
+
+// Parses an inclusive range of values in format <start>:<end>:<step>
+// or a single value
+fn parse_range(input: &str) -> Result<Vec<u32>, ()> {
+    let args: Vec<&str> = input.split(':').collect();
+
+    let mut values = Vec::with_capacity(args.len());
+    for arg in &args {
+        match arg.parse::<u32>() {
+            Ok(value) => values.push(value),
+            Err(_) => { return Err(()); }
+        }
+    }
+
+    let (start, end, step) = match values.len() {
+        1 => (values[0], values[0], 1),
+        3 => (values[0], values[1], values[2]),
+        _ => { return Err(()); }
+    };
+
+    if start > end || step == 0 {
+        return Err(());
+    }
+
+    Ok((start..=end).step_by(step as usize).collect())
+}
+
+fn range_arg(matches: &ArgMatches, name: &str, default: &str) -> Vec<u32> {
+    let input = matches.value_of(name).unwrap_or(default);
+    match parse_range(input) {
+        Ok(values) => values,
+        Err(()) => {
+            println_stderr!("error: --{} has bad format", name.replace('_', "-"));
+            exit(1);
+        }
+    }
+}
+
+fn count_arg(matches: &ArgMatches, name: &str, default: u32) -> u32 {
+    match matches.value_of(name) {
+        None => default,
+        Some(input) => match input.parse::<u32>() {
+            Ok(value) => value,
+            Err(_) => {
+                println_stderr!("error: --{} must be a non-negative integer",
+                                name.replace('_', "-"));
+                exit(1);
+            }
+        },
+    }
+}
+
+fn percentage_arg(matches: &ArgMatches, name: &str) -> f64 {
+    match matches.value_of(name) {
+        None => 0f64,
+        Some(input) => match input.parse::<f64>() {
+            Ok(value) if value >= 0f64 => value,
+            _ => {
+                println_stderr!("error: --{} must be a non-negative number",
+                                name.replace('_', "-"));
+                exit(1);
+            }
+        },
+    }
+}
+
+// Builds every resign rule from the ranges in `evals_arg` and `counts_arg`
+fn resign_rules_arg(matches: &ArgMatches,
+                    evals_arg: &str, default_evals: &str,
+                    counts_arg: &str, default_counts: &str) -> Vec<ResignRule> {
+
+    let evals = range_arg(matches, evals_arg, default_evals);
+    let counts = range_arg(matches, counts_arg, default_counts);
+
+    if evals.contains(&0) {
+        println_stderr!("error: Resign rule evaluation must be positive");
+        exit(1);
+    }
+
+    if counts.contains(&0) {
+        println_stderr!("error: Resign rule count must be positive");
+        exit(1);
+    }
+
+    let mut rules = Vec::with_capacity(evals.len() * counts.len());
+    for &eval in &evals {
+        for &count in &counts {
+            rules.push(ResignRule::new(eval as i32, count).unwrap());
+        }
+    }
+
+    rules
+}
+
+// Builds every draw rule from the ranges in `from_moves_arg`,
+// `evals_arg` and `counts_arg`
+fn draw_rules_arg(matches: &ArgMatches,
+                  from_moves_arg: &str, default_from_moves: &str,
+                  evals_arg: &str, default_evals: &str,
+                  counts_arg: &str, default_counts: &str) -> Vec<DrawRule> {
+
+    let from_moves = range_arg(matches, from_moves_arg, default_from_moves);
+    let evals = range_arg(matches, evals_arg, default_evals);
+    let counts = range_arg(matches, counts_arg, default_counts);
+
+    if from_moves.contains(&0) {
+        println_stderr!("error: Draw rule move from must be positive");
+        exit(1);
+    }
+
+    if evals.contains(&0) {
+        println_stderr!("error: Draw rule evaluation must be positive");
+        exit(1);
+    }
+
+    if counts.contains(&0) {
+        println_stderr!("error: Draw rule count must be positive");
+        exit(1);
+    }
+
+    let mut rules =
+        Vec::with_capacity(from_moves.len() * evals.len() * counts.len());
+    for &from_move in &from_moves {
+        for &eval in &evals {
+            for &count in &counts {
+                rules.push(DrawRule::new(from_move, eval as i32, count).unwrap());
+            }
+        }
+    }
+
+    rules
+}
+
+// Wraps each of `rules` in a candidate with an empty summary
+fn candidates<R>(rules: Vec<R>) -> Vec<Candidate<R>> {
+    rules.into_iter()
+        .map(|rule| Candidate { rule: rule, summary: RuleTestSummary::default() })
+        .collect()
+}
+
+// Adjudicates every game for each of `candidates` with `adjudicate`.
+// Each game is dropped once all the candidates have seen it, so only
+// their totals are kept however many games there are. Returns the
+// number of games.
+fn try_candidates<R, F>(matches: &ArgMatches, candidates: &mut [Candidate<R>], adjudicate: F)
+    -> usize
+    where F: Fn(&R, &GameData) -> AdjudicationOutcome {
+
+    let mut games = stream_games(matches, pgn_files(matches));
+    let mut num_games = 0;
+
+    for (_, _, game) in games.by_ref() {
+        for candidate in candidates.iter_mut() {
+            candidate.summary.add(&adjudicate(&candidate.rule, &game));
+        }
+        num_games += 1;
+    }
+
+    games.print_skipped();
+    num_games
+}
+
+// Orders candidates from best to worst: most time saved,
+// then fewest wrong adjudications, then lowest MSE
+fn compare_by_time_saved<R>(a: &Candidate<R>, b: &Candidate<R>) -> Ordering {
+    b.summary.time_saved().cmp(&a.summary.time_saved())
+        .then(a.summary.num_wrong().cmp(&b.summary.num_wrong()))
+        .then(a.summary.mse().partial_cmp(&b.summary.mse()).unwrap_or(Ordering::Equal))
+}
+
+// Orders candidates from best to worst: lowest MSE,
+// then most time saved
+fn compare_by_mse<R>(a: &Candidate<R>, b: &Candidate<R>) -> Ordering {
+    a.summary.mse().partial_cmp(&b.summary.mse()).unwrap_or(Ordering::Equal)
+        .then(b.summary.time_saved().cmp(&a.summary.time_saved()))
+}
+
+fn print_table<R: Display>(candidates: &[Candidate<R>], header: &str, top: u32) {
+    let shown = &candidates[..candidates.len().min(top as usize)];
+
+    let width = shown.iter()
+        .map(|candidate| candidate.rule.to_string().len())
+        .fold(max(16, header.len()), max);
+
+    println!("{:<width$} {:>11} {:>6} {:>22} {:>9}",
+             header, "Adjudicated", "Wrong", "Time saved", "MSE",
+             width = width);
+
+    for candidate in shown {
+        let summary = &candidate.summary;
+        println!("{:<width$} {:>11} {:>6} {:>22} {:>9.6}",
+                 candidate.rule.to_string(),
+                 summary.num(),
+                 summary.num_wrong(),
+                 format!("{} ({:.2}%)",
+                         format_time(summary.time_saved()),
+                         summary.time_saved_perc()),
+                 summary.mse(),
+                 width = width);
+    }
+}
+
+fn print_recommendations<R: Display>(
+    mut candidates: Vec<Candidate<R>>,
+    games: usize,
+    max_wrong: u32,
+    top: u32) {
+
+    let tested = candidates.len();
+    candidates.retain(|candidate| candidate.summary.num_wrong() <= max_wrong);
+    candidates.sort_by(compare_by_time_saved);
+
+    println!("Games: {}", games);
+    println!("Rules tested: {}", tested);
+    println!("Rules within error budget (max {} wrong): {}", max_wrong, candidates.len());
+    println!("");
+
+    if candidates.is_empty() {
+        println!("No rule is within the error budget.");
+        return;
+    }
+
+    print_table(&candidates, "Rule", top);
+}
+
+pub fn resign_main(matches: &ArgMatches) {
+    let rules = resign_rules_arg(matches,
+                                 "evals", "100:1000:50",
+                                 "counts", "1:10:1");
+    let max_wrong = count_arg(matches, "max_wrong", 0);
+    let top = count_arg(matches, "top", 10);
+
+    let draw_rule = DrawRule::none();
+
+    let mut candidates = candidates(rules);
+    let games = try_candidates(matches, &mut candidates,
+                               |rule, game| adjudicate_game(game, rule, &draw_rule));
+
+    print_recommendations(candidates, games, max_wrong, top);
+}
+
+pub fn draw_main(matches: &ArgMatches) {
+    let rules = draw_rules_arg(matches,
+                               "from_moves", "20:80:10",
+                               "evals", "10:50:10",
+                               "counts", "1:10:1");
+    let max_wrong = count_arg(matches, "max_wrong", 0);
+    let top = count_arg(matches, "top", 10);
+
+    let resign_rule = ResignRule::none();
+
+    let mut candidates = candidates(rules);
+    let games = try_candidates(matches, &mut candidates,
+                               |rule, game| adjudicate_game(game, &resign_rule, rule));
+
+    print_recommendations(candidates, games, max_wrong, top);
+}
+
+// Tries every pair of rules from the grids given by the `resign_*`
+// and `draw_*` args. Returns the candidates and the number of games.
+fn rule_pair_candidates(matches: &ArgMatches) -> (Vec<Candidate<RulePair>>, usize) {
+
+    let resign_rules = resign_rules_arg(matches,
+                                        "resign_evals", "200:1000:100",
+                                        "resign_counts", "2:8:2");
+    let draw_rules = draw_rules_arg(matches,
+                                    "draw_from_moves", "20:80:20",
+                                    "draw_evals", "10:50:20",
+                                    "draw_counts", "4:12:4");
+
+    let mut rules = Vec::with_capacity(resign_rules.len() * draw_rules.len());
+    for resign_rule in &resign_rules {
+        for draw_rule in &draw_rules {
+            rules.push(RulePair { resign: resign_rule.clone(), draw: draw_rule.clone() });
+        }
+    }
+
+    let mut candidates = candidates(rules);
+    let games = try_candidates(matches, &mut candidates,
+                               |rule, game| adjudicate_game(game, &rule.resign, &rule.draw));
+
+    (candidates, games)
+}
+
+pub fn optimize_main(matches: &ArgMatches) {
+    let objective = match matches.value_of("objective") {
+        None | Some("time") => Objective::TimeSaved,
+        Some("mse") => Objective::Mse,
+        Some(_) => unreachable!(),
+    };
+
+    // Ranking by MSE already penalizes wrong adjudications
+    // so there's no error budget unless one is given
+    let max_wrong = match objective {
+        Objective::TimeSaved => count_arg(matches, "max_wrong", 0),
+        Objective::Mse => count_arg(matches, "max_wrong", u32::max_value()),
+    };
+    let min_time_saved_perc = percentage_arg(matches, "min_time_saved");
+    let top = count_arg(matches, "top", 10);
+
+    let (mut candidates, games) = rule_pair_candidates(matches);
+
+    let tested = candidates.len();
+    candidates.retain(|candidate|
+        candidate.summary.num_wrong() <= max_wrong &&
+        candidate.summary.time_saved_perc() >= min_time_saved_perc);
+
+    match objective {
+        Objective::TimeSaved => candidates.sort_by(compare_by_time_saved),
+        Objective::Mse => candidates.sort_by(compare_by_mse),
+    }
+
+    println!("Games: {}", games);
+    println!("Rule pairs tested: {}", tested);
+    match objective {
+        Objective::TimeSaved => println!("Objective: most time saved"),
+        Objective::Mse => println!("Objective: lowest MSE"),
+    }
+    if max_wrong != u32::max_value() {
+        println!("  Max wrong: {}", max_wrong);
+    }
+    if min_time_saved_perc > 0f64 {
+        println!("  Min time saved: {:.2}%", min_time_saved_perc);
+    }
+    println!("Rule pairs meeting the constraints: {}", candidates.len());
+    println!("");
+
+    if candidates.is_empty() {
+        println!("No rule pair meets the constraints.");
+        return;
+    }
+
+    print_table(&candidates, "Resign/Draw rules", top);
+}
+
+// Returns true if `a` is at least as good as `b` on time saved and
+// on the error measure, and better on one of them
+fn dominates<R>(a: &Candidate<R>, b: &Candidate<R>, error: &ErrorMeasure) -> bool {
+    let (a_time, b_time) = (a.summary.time_saved(), b.summary.time_saved());
+    let (a_error, b_error) = match *error {
+        ErrorMeasure::Wrong =>
+            (a.summary.num_wrong() as f64, b.summary.num_wrong() as f64),
+        ErrorMeasure::Mse => (a.summary.mse(), b.summary.mse()),
+    };
+
+    a_time >= b_time && a_error <= b_error &&
+        (a_time > b_time || a_error < b_error)
+}
+
+// Returns the candidates that no other candidate dominates,
+// ordered from least to most error
+fn pareto_frontier<R>(candidates: Vec<Candidate<R>>, error: &ErrorMeasure)
+    -> Vec<Candidate<R>> {
+
+    let dominated: Vec<bool> = candidates.iter()
+        .map(|candidate| candidates.iter()
+             .any(|other| dominates(other, candidate, error)))
+        .collect();
+
+    let mut frontier: Vec<Candidate<R>> = candidates.into_iter()
+        .zip(dominated)
+        .filter(|&(_, dominated)| !dominated)
+        .map(|(candidate, _)| candidate)
+        .collect();
+
+    // The sort is stable so rule pairs with the same results
+    // stay in the order they were tried
+    frontier.sort_by(|a, b| {
+        let order = match *error {
+            ErrorMeasure::Wrong => a.summary.num_wrong().cmp(&b.summary.num_wrong()),
+            ErrorMeasure::Mse => a.summary.mse().partial_cmp(&b.summary.mse())
+                                  .unwrap_or(Ordering::Equal),
+        };
+        order.then(a.summary.time_saved().cmp(&b.summary.time_saved()))
+    });
+    frontier
+}
+
+pub fn frontier_main(matches: &ArgMatches) {
+    let error = match matches.value_of("error") {
+        None | Some("wrong") => ErrorMeasure::Wrong,
+        Some("mse") => ErrorMeasure::Mse,
+        Some(_) => unreachable!(),
+    };
+
+    let (candidates, games) = rule_pair_candidates(matches);
+    let tested = candidates.len();
+    let frontier = pareto_frontier(candidates, &error);
+
+    match matches.value_of("format") {
+        None | Some("table") => {
+            println!("Games: {}", games);
+            println!("Rule pairs tested: {}", tested);
+
+            let measure = match error {
+                ErrorMeasure::Wrong => "wrong",
+                ErrorMeasure::Mse => "MSE",
+            };
+            println!("Rule pairs on the time saved/{} frontier: {}", measure, frontier.len());
+            println!("");
+
+            print_table(&frontier, "Resign/Draw rules", u32::max_value());
+        }
+        Some("csv") => {
+            println!("resign_rule, draw_rule, adjudicated, wrong, \
+                      time_saved, time_saved_perc, mse");
+
+            for candidate in &frontier {
+                let summary = &candidate.summary;
+                println!("{}, {}, {}, {}, {}, {:.4}, {:.6}",
+                         candidate.rule.resign,
+                         candidate.rule.draw,
+                         summary.num(),
+                         summary.num_wrong(),
+                         summary.time_saved(),
+                         summary.time_saved_perc(),
+                         summary.mse());
+            }
+        }
+        Some(_) => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_range;
+
+    #[test]
+    fn range_parsing() {
+        assert_eq!(parse_range("100:300:100"), Ok(vec![100, 200, 300]));
+        assert_eq!(parse_range("1:10:4"), Ok(vec![1, 5, 9]));
+        assert_eq!(parse_range("7"), Ok(vec![7]));
+        assert_eq!(parse_range("4294967295"), Ok(vec![4294967295]));
+        assert_eq!(parse_range("4294967293:4294967295:2"), Ok(vec![4294967293, 4294967295]));
+        assert_eq!(parse_range("5:1:1"), Err(()));
+        assert_eq!(parse_range("1:5:0"), Err(()));
+        assert_eq!(parse_range("1:5"), Err(()));
+        assert_eq!(parse_range("a:5:1"), Err(()));
+    }
+}
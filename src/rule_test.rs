@@ -1,18 +1,16 @@
+use std::cmp::max;
 use std::io::Write;
 use std::process::exit;
 
 use clap::ArgMatches;
 
-use super::{GameData, read_games};
+use cara::{AdjudicationOutcome, DrawClaim, DrawRule, DrawRuleParsingError, GameData, GameStats,
+           ResignRule, ResignRuleParsingError, RulePriority, RuleType, TablebaseRule,
+           adjudicate_game_with, find_draw_claim};
 
-#[derive(Debug, Clone)]
-struct GameStats {
-    length: u32,
-    time: u32,
-    score10: u32,
-}
+use input::{pgn_files, stream_games};
 
-fn format_time(milliseconds: u32) -> String {
+pub fn format_time(milliseconds: u64) -> String {
     let mut value = milliseconds;
 
     let ms = value % 1000;
@@ -29,143 +27,28 @@ fn format_time(milliseconds: u32) -> String {
     format!("{}:{:02}:{:02}.{:03}", h, m, s, ms)
 }
 
-// An engine resigns if his eval is worse than `-eval`
-// for `count` of his moves with this rule
-pub struct ResignRule {
-    eval: i32,
-    count: u32,
-}
-
-impl ResignRule {
-    fn new(eval: i32, count: u32) -> Result<ResignRule, &'static str> {
-        if eval <= 0 {
-            return Err("eval is out of range.");
-        }
-
-        if count == 0 {
-            return Err("count is out of range.");
-        }
-
-        Ok(ResignRule {
-            eval: eval,
-            count: count,
-        })
+// The resign and draw rules, given either as --resign and --draw or
+// as the positional args after the first file
+fn rule_args<'a>(matches: &'a ArgMatches) -> (&'a str, &'a str) {
+    if matches.is_present("resign_option") {
+        return (matches.value_of("resign_option").unwrap(),
+                matches.value_of("draw_option").unwrap());
     }
-}
-
-// The game is a draw the score is within [-eval, eval]
-// for `count` of his moves with this rule. The rule
-// may only be applied on or after `from_move`.
-pub struct DrawRule {
-    from_move: u32,
-    eval: i32,
-    count: u32,
-}
-
-impl DrawRule {
-    fn new(from_move: u32, eval: i32, count: u32) -> Result<DrawRule, &'static str> {
-        if from_move == 0 {
-            return Err("from_move is out of range.");
-        }
-
-        if eval < 0 {
-            return Err("eval is out of range.");
-        }
-
-        if count == 0 {
-            return Err("count is out of range.");
-        }
 
-        Ok(DrawRule {
-            from_move: from_move,
-            eval: eval,
-            count: count,
-        })
+    match (matches.value_of("resign_rule"), matches.value_of("draw_rule")) {
+        (Some(resign_rule), Some(draw_rule)) => (resign_rule, draw_rule),
+        _ => {
+            println_stderr!("error: Missing <resign_rule> and <draw_rule>");
+            exit(1);
+        },
     }
 }
 
-enum ResignRuleParsingError {
-    BadFormat,
-    NonPositiveEval,
-    NonPositiveCount
-}
-
-fn parse_resign_rule(input: &str) -> Result<ResignRule, ResignRuleParsingError> {
-    if input == "none" {
-        //Return a rule that will never be applied
-        return Ok(ResignRule::new(10000, 10000).unwrap());
-    }
-
-    let args: Vec<&str> = input.split('/').collect();
-    let eval = match args[0].parse::<i32>() {
-        Ok(value) => value,
-        Err(_) => { return Err(ResignRuleParsingError::BadFormat); }
-    };
-
-    if eval <= 0 {
-        return Err(ResignRuleParsingError::NonPositiveEval);
-    }
-
-    let count = match args[1].parse::<u32>() {
-        Ok(value) => value,
-        Err(_) => { return Err(ResignRuleParsingError::BadFormat); }
-    };
-
-    if count == 0 {
-        return Err(ResignRuleParsingError::NonPositiveCount);
-    }
-
-    Ok(ResignRule::new(eval, count).unwrap())
-}
-
-enum DrawRuleParsingError {
-    BadFormat,
-    NonPositiveFromMove,
-    NegativeEval,
-    NonPositiveCount
-}
-
-fn parse_draw_rule(input: &str) -> Result<DrawRule, DrawRuleParsingError> {
-    if input == "none" {
-        //Return a rule that will never be applied
-        return Ok(DrawRule::new(10000, 0, 10000).unwrap());
-    }
-
-    let args1: Vec<&str> = input.split(':').collect();
-    let from_move = match args1[0].parse::<u32>() {
-        Ok(value) => value,
-        Err(_) => { return Err(DrawRuleParsingError::BadFormat); }
-    };
-
-    if from_move == 0 {
-        return Err(DrawRuleParsingError::NonPositiveFromMove);
-    }
-
-    let args2: Vec<&str> = args1[1].split('/').collect();
-    let eval = match args2[0].parse::<i32>() {
-        Ok(value) => value,
-        Err(_) => { return Err(DrawRuleParsingError::BadFormat); }
-    };
-
-    if eval <= 0 {
-        return Err(DrawRuleParsingError::NegativeEval);
-    }
-
-    let count = match args2[1].parse::<u32>() {
-        Ok(value) => value,
-        Err(_) => { return Err(DrawRuleParsingError::BadFormat); }
-    };
-
-    if count == 0 {
-        return Err(DrawRuleParsingError::NonPositiveCount);
-    }
-
-    Ok(DrawRule::new(from_move, eval, count).unwrap())
-}
-
 pub fn main(matches: &ArgMatches) {
+    let (resign_arg, draw_arg) = rule_args(matches);
+
     let resign_rule =
-        match parse_resign_rule(matches.value_of("resign_rule").unwrap()) {
+        match resign_arg.parse::<ResignRule>() {
             Ok(rule) => rule,
             Err(ResignRuleParsingError::BadFormat) => {
                 println_stderr!("error: Resign rule has bad format");
@@ -179,10 +62,14 @@ pub fn main(matches: &ArgMatches) {
                 println_stderr!("error: Resign rule count must be positive");
                 exit(1);
             },
+            Err(ResignRuleParsingError::PercentOutOfRange) => {
+                println_stderr!("error: Resign rule percentage must be between 0 and 100");
+                exit(1);
+            },
         };
 
     let draw_rule =
-        match parse_draw_rule(matches.value_of("draw_rule").unwrap()) {
+        match draw_arg.parse::<DrawRule>() {
             Ok(rule) => rule,
             Err(DrawRuleParsingError::BadFormat) => {
                 println_stderr!("error: Draw rule has bad format");
@@ -200,215 +87,755 @@ pub fn main(matches: &ArgMatches) {
                 println_stderr!("error: Draw rule count must be positive");
                 exit(1);
             },
+            Err(DrawRuleParsingError::PercentOutOfRange) => {
+                println_stderr!("error: Draw rule percentage must be between 0 and 100");
+                exit(1);
+            },
         };
 
-    let game_data = read_games(&matches);
+    let tablebase_rule = matches.value_of("tablebase").map(|value| {
+        match value.parse::<u32>().map(TablebaseRule::new) {
+            Ok(Ok(rule)) => rule,
+            Ok(Err(_)) => {
+                println_stderr!("error: Tablebase piece count must be at least 3");
+                exit(1);
+            },
+            Err(_) => {
+                println_stderr!("error: Tablebase piece count has bad format");
+                exit(1);
+            },
+        }
+    });
+
+    let priority = match matches.value_of("priority") {
+        None | Some("draw") => RulePriority::Draw,
+        Some("resign") => RulePriority::Resign,
+        Some(_) => unreachable!(),
+    };
+
+    let format = match matches.value_of("format") {
+        None | Some("text") => OutputFormat::Text,
+        Some("json") => OutputFormat::Json,
+        Some(_) => unreachable!(),
+    };
+
+    let paths = pgn_files(matches);
+    let files: Vec<String> = paths.iter()
+        .map(|path| path.display().to_string())
+        .collect();
+
+    let mut games = stream_games(matches, paths);
 
-    test_rule(
-        &game_data,
-        &resign_rule,
-        &draw_rule,
-        matches.is_present("verbose"));
+    let report = Report {
+        format: format,
+        verbose: matches.is_present("verbose"),
+        per_file: matches.is_present("per_file"),
+        per_engine: matches.is_present("per_engine"),
+        tablebase_savings: matches.is_present("tablebase_savings"),
+        draw_claims: matches.is_present("draw_claims"),
+    };
+
+    test_rule(games.by_ref(),
+              &files,
+              &resign_rule,
+              &draw_rule,
+              tablebase_rule.as_ref(),
+              priority,
+              &report);
+
+    games.print_skipped();
 }
 
-fn test_rule(games: &Vec<GameData>,
-                 resign_rule: &ResignRule,
-                 draw_rule: &DrawRule,
-                 verbose: bool) {
+enum OutputFormat {
+    Text,
+    Json,
+}
 
-    let mut actual_time = 0;
-    let mut adjudicated_time = 0;
+// What `test_rule` shows besides the summary totals
+struct Report {
+    format: OutputFormat,
+    verbose: bool,
+    per_file: bool,
+    per_engine: bool,
+    tablebase_savings: bool,
+    draw_claims: bool,
+}
 
-    let mut resign_num = 0;
-    let mut resign_num_wrong = 0;
-    let mut resign_time_saved = 0;
-    let mut resign_squared_error10 = 0;
+// The piece counts that Syzygy tablebases are available for
+const TABLEBASE_SIZES: [u32; 5] = [3, 4, 5, 6, 7];
 
-    let mut draw_num = 0;
-    let mut draw_num_wrong = 0;
-    let mut draw_time_saved = 0;
-    let mut draw_squared_error10 = 0;
+// Adjudication totals for a single rule type
+#[derive(Debug, Clone, Default)]
+pub struct RuleStats {
+    pub num: u32,
+    pub num_wrong: u32,
+    pub time_saved: u64,
+    pub squared_error10: u32,
+}
 
+impl RuleStats {
+    fn add(&mut self, outcome: &AdjudicationOutcome) {
+        self.num += 1;
+        self.num_wrong += (!outcome.correctly_adjudicated()) as u32;
+        self.time_saved += outcome.time_saved();
+        self.squared_error10 += outcome.squared_error10();
+    }
 
-    if verbose {
-        println!("game, actual_length, actual_time, actual_score, \
-                  rule_applied, adjudicated_length, adjudicated_time, adjudicated_score");
+    pub fn time_saved_perc(&self, actual_time: u64) -> f64 {
+        self.time_saved as f64 / actual_time as f64 * 100f64
     }
 
-    for (index, game) in games.iter().enumerate() {
+    pub fn mse(&self, games: u32) -> f64 {
+        self.squared_error10 as f64 / 100f64 / (games as f64)
+    }
+}
 
-        let outcome = adjudicate_game(game, resign_rule, draw_rule);
+// The totals that `test_rule` reports for a pair of rules
+#[derive(Debug, Clone, Default)]
+pub struct RuleTestSummary {
+    pub games: u32,
+    pub actual_time: u64,
+    pub adjudicated_time: u64,
+    pub resign: RuleStats,
+    pub draw: RuleStats,
+    pub tablebase: RuleStats,
+    // Games where the resign and draw rules applied on the same ply
+    pub both_rules: u32,
+    // Games left out of the times as some of theirs aren't known
+    pub untimed_games: u32,
+}
 
+impl RuleTestSummary {
+    pub fn add(&mut self, outcome: &AdjudicationOutcome) {
         match outcome.rule_applied {
-            Some(RuleType::Resign) => {
-                resign_num += 1;
-                resign_num_wrong += (!outcome.correctly_adjudicated()) as u32;
-                resign_time_saved += outcome.time_saved();
-                resign_squared_error10 += outcome.squared_error10();
-            }
-            Some(RuleType::Draw) => {
-                draw_num += 1;
-                draw_num_wrong += (!outcome.correctly_adjudicated()) as u32;
-                draw_time_saved += outcome.time_saved();
-                draw_squared_error10 += outcome.squared_error10();
-            }
+            Some(RuleType::Resign) => self.resign.add(outcome),
+            Some(RuleType::Draw) => self.draw.add(outcome),
+            Some(RuleType::Tablebase) => self.tablebase.add(outcome),
             None => { }
         }
 
-        actual_time += outcome.actual.time;
-        adjudicated_time += outcome.adjudicated.time;
-
-        if verbose {
-            println!("{}, {}, {}, {}, {}, {}, {}, {}",
-                     index + 1,
-                     outcome.actual.length,
-                     outcome.actual.time,
-                     outcome.actual.score10 as f32 / 10f32,
-                     match outcome.rule_applied {
-                         Some(RuleType::Resign) => "R",
-                         Some(RuleType::Draw) => "D",
-                         None => "-",
-                     },
-                     outcome.adjudicated.length,
-                     outcome.adjudicated.time,
-                     outcome.adjudicated.score10 as f32 / 10f32);
+        self.both_rules += outcome.both_rules_applied as u32;
+        self.games += 1;
+        if outcome.time_known {
+            self.actual_time += outcome.actual.time;
+            self.adjudicated_time += outcome.adjudicated.time;
+        } else {
+            self.untimed_games += 1;
         }
     }
 
-    if verbose {
-        println!("");
+    pub fn num(&self) -> u32 {
+        self.resign.num + self.draw.num + self.tablebase.num
     }
 
-    let adjudicated_num = resign_num + draw_num;
-    let adjudciated_num_wrong = resign_num_wrong + draw_num_wrong;
+    pub fn num_wrong(&self) -> u32 {
+        self.resign.num_wrong + self.draw.num_wrong + self.tablebase.num_wrong
+    }
 
-    println!("Games: {}", games.len());
-    println!("Adjudicated: {} ({} wrong)", adjudicated_num, adjudciated_num_wrong);
-    println!("  Resign: {} ({} wrong)", resign_num, resign_num_wrong);
-    println!("  Draw: {} ({} wrong)", draw_num, draw_num_wrong);
-    println!("");
+    pub fn time_saved(&self) -> u64 {
+        self.resign.time_saved + self.draw.time_saved + self.tablebase.time_saved
+    }
 
-    let time_saved = resign_time_saved + draw_time_saved;
-    let time_saved_perc = time_saved as f64 / actual_time as f64 * 100f64;
+    pub fn time_saved_perc(&self) -> f64 {
+        self.time_saved() as f64 / self.actual_time as f64 * 100f64
+    }
 
-    let resign_time_saved_perc = resign_time_saved as f64 / actual_time as f64 * 100f64;
-    let draw_time_saved_perc = draw_time_saved as f64 / actual_time as f64 * 100f64;
+    pub fn mse(&self) -> f64 {
+        (self.resign.squared_error10 +
+         self.draw.squared_error10 +
+         self.tablebase.squared_error10) as f64
+        / 100f64
+        / (self.games as f64)
+    }
+}
 
-    println!("Total Time: {}", format_time(actual_time));
-    println!("After Adjudication: {}", format_time(adjudicated_time));
-    println!("Time saved: {} ({:.2}%)", format_time(time_saved as u32), time_saved_perc);
-    println!("  Resign: {} ({:.2}%)", format_time(resign_time_saved), resign_time_saved_perc);
-    println!("  Draw: {} ({:.2}%)", format_time(draw_time_saved), draw_time_saved_perc);
-    println!("Note: 'Time saved' excludes incorrectly adjudicated games");
-    println!("");
+// Adjudication totals for the games an engine played with one color
+#[derive(Debug, Clone, Default)]
+pub struct ColorStats {
+    pub games: u32,
+    pub actual_time: u64,
+    // Games where the engine was made to resign
+    pub resigned: u32,
+    pub resigned_wrong: u32,
+    pub drawn: u32,
+    pub drawn_wrong: u32,
+    pub time_saved: u64,
+}
 
-    let mse =
-        (resign_squared_error10 as f64 + draw_squared_error10 as f64)
-        / 100f64
-        / (games.len() as f64);
+impl ColorStats {
+    // `losing_score10` is the score when the engine's color loses
+    fn add(&mut self, outcome: &AdjudicationOutcome, losing_score10: u32) {
+        let wrong = !outcome.correctly_adjudicated() as u32;
 
-    let resign_mse = resign_squared_error10 as f64 / 100f64 / (games.len() as f64);
-    let draw_mse = draw_squared_error10 as f64 / 100f64 / (games.len() as f64);
+        match outcome.rule_applied {
+            Some(RuleType::Resign) if outcome.adjudicated.score10 == losing_score10 => {
+                self.resigned += 1;
+                self.resigned_wrong += wrong;
+            },
+            Some(RuleType::Draw) => {
+                self.drawn += 1;
+                self.drawn_wrong += wrong;
+            },
+            _ => { },
+        }
 
-    println!("Mean Squared Error: {:.6}", mse);
-    println!("  Resign: {:.6}", resign_mse);
-    println!("  Draw: {:.6}", draw_mse);
-    println!("Root MSE: {:.3}", mse.powf(0.5));
-}
+        self.games += 1;
+        if outcome.time_known {
+            self.actual_time += outcome.actual.time;
+        }
+        self.time_saved += outcome.time_saved();
+    }
 
-fn adjudicate_game(
-    game: &GameData,
-    resign_rule: &ResignRule,
-    draw_rule: &DrawRule) -> AdjudicationOutcome {
+    fn combine(&self, other: &ColorStats) -> ColorStats {
+        ColorStats {
+            games: self.games + other.games,
+            actual_time: self.actual_time + other.actual_time,
+            resigned: self.resigned + other.resigned,
+            resigned_wrong: self.resigned_wrong + other.resigned_wrong,
+            drawn: self.drawn + other.drawn,
+            drawn_wrong: self.drawn_wrong + other.drawn_wrong,
+            time_saved: self.time_saved + other.time_saved,
+        }
+    }
 
-    let mut resign_counts: [u32; 2] = [0, 0];
-    let mut draw_count = 0;
+    pub fn time_saved_perc(&self) -> f64 {
+        self.time_saved as f64 / self.actual_time as f64 * 100f64
+    }
+}
 
-    let score10 = game.score10;
+// The totals for an engine, split by the color it played
+#[derive(Debug)]
+pub struct EngineSummary {
+    pub name: String,
+    pub colors: [ColorStats; 2],
+}
 
-    let mut total_time = 0;
-    let mut rule_applied: Option<RuleType> = None;
-    let mut adjudicated_outcome: Option<GameStats> = None;
+// The totals for each engine in the order they're first seen
+#[derive(Debug, Default)]
+pub struct EngineSummaries {
+    pub engines: Vec<EngineSummary>,
+}
 
-    for (ply0, move_data) in game.move_data.iter().enumerate() {
-        total_time += move_data.time;
+impl EngineSummaries {
+    fn add(&mut self, game: &GameData, outcome: &AdjudicationOutcome) {
+        let players = [game.white(), game.black()];
+
+        for (color, player) in players.iter().enumerate() {
+            let name = player.unwrap_or("?");
+
+            let index = match self.engines.iter().position(|engine| engine.name == name) {
+                Some(index) => index,
+                None => {
+                    self.engines.push(EngineSummary {
+                        name: name.to_string(),
+                        colors: Default::default(),
+                    });
+                    self.engines.len() - 1
+                }
+            };
+
+            self.engines[index].colors[color].add(outcome, [0, 10][color]);
+        }
+    }
+}
 
-        if adjudicated_outcome.is_none() {
+// The games that went on after the rules of chess allowed a draw
+#[derive(Debug, Clone, Default)]
+pub struct DrawClaimStats {
+    pub num: u32,
+    pub fifty_moves: u32,
+    pub repetition: u32,
+    pub insufficient_material: u32,
+    // The games that weren't drawn in the end
+    pub num_not_drawn: u32,
+    // The time taken by the moves after the draw
+    pub time_wasted: u64,
+}
 
-            if move_data.eval.abs() <= draw_rule.eval {
-                draw_count += 1;
-            } else {
-                draw_count = 0;
-            }
+impl DrawClaimStats {
+    fn add(&mut self, game: &GameData) {
+        let (ply0, claim) = match find_draw_claim(game) {
+            Some((ply0, claim)) if ply0 + 1 < game.move_data.len() => (ply0, claim),
+            _ => { return; }
+        };
 
-            if (ply0 as u32 + 1) / 2 >= draw_rule.from_move &&
-               draw_count >= 2 * draw_rule.count {
+        self.num += 1;
+        match claim {
+            DrawClaim::FiftyMoves => { self.fifty_moves += 1; },
+            DrawClaim::Repetition => { self.repetition += 1; },
+            DrawClaim::InsufficientMaterial => { self.insufficient_material += 1; },
+        }
+        self.num_not_drawn += (game.score10 != 5) as u32;
+        if game.time_known {
+            self.time_wasted += game.move_data[ply0 + 1..].iter()
+                .map(|move_data| move_data.time as u64)
+                .sum::<u64>();
+        }
+    }
 
-                rule_applied = Some(RuleType::Draw);
-                adjudicated_outcome = Some(GameStats {
-                    length: ply0 as u32 + 1,
-                    time: total_time,
-                    score10: 5,
-                });
-                continue;
-           }
+    pub fn time_wasted_perc(&self, actual_time: u64) -> f64 {
+        self.time_wasted as f64 / actual_time as f64 * 100f64
+    }
+}
 
-            if move_data.eval <= -(resign_rule.eval as i32) {
-                resign_counts[ply0 % 2] += 1;
-            } else {
-                resign_counts[ply0 % 2] = 0;
-            }
+// Formats a number for JSON output, which has no NaN or infinity
+fn json_f64(value: f64, precision: usize) -> String {
+    if value.is_finite() {
+        format!("{:.*}", precision, value)
+    } else {
+        "null".to_string()
+    }
+}
 
-            if resign_counts[ply0 % 2] == resign_rule.count {
+fn json_string(value: &str) -> String {
+    let mut result = String::with_capacity(value.len() + 2);
 
-                rule_applied = Some(RuleType::Resign);
-                adjudicated_outcome = Some(GameStats {
-                    length: ply0 as u32 + 1,
-                    time: total_time,
-                    score10: [0, 10][ply0 % 2],
-                });
-            }
+    result.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
         }
     }
+    result.push('"');
 
-    let actual_outcome = GameStats {
-        length: game.move_data.len() as u32,
-        time: total_time,
-        score10: score10,
+    result
+}
+
+fn json_rule_applied(rule_applied: &Option<RuleType>) -> &'static str {
+    match *rule_applied {
+        Some(RuleType::Resign) => "\"resign\"",
+        Some(RuleType::Draw) => "\"draw\"",
+        Some(RuleType::Tablebase) => "\"tablebase\"",
+        None => "null",
+    }
+}
+
+fn json_game_stats(stats: &GameStats) -> String {
+    format!("{{ \"length\": {}, \"time\": {}, \"score\": {} }}",
+            stats.length,
+            stats.time,
+            stats.score10 as f32 / 10f32)
+}
+
+fn json_rule_stats(stats: &RuleStats, summary: &RuleTestSummary) -> String {
+    format!("{{ \"num\": {}, \"num_wrong\": {}, \"time_saved\": {}, \
+             \"time_saved_perc\": {}, \"mse\": {} }}",
+            stats.num,
+            stats.num_wrong,
+            stats.time_saved,
+            json_f64(stats.time_saved_perc(summary.actual_time), 4),
+            json_f64(stats.mse(summary.games), 6))
+}
+
+// The file is only named when there's more than one
+fn print_json_outcome(index: usize,
+                      file: Option<&str>,
+                      game_number: u32,
+                      outcome: &AdjudicationOutcome) {
+    if index > 0 {
+        println!(",");
+    }
+
+    let file = match file {
+        Some(file) => format!("\"file\": {}, ", json_string(file)),
+        None => String::new(),
     };
 
-    AdjudicationOutcome {
-        adjudicated: adjudicated_outcome.unwrap_or(actual_outcome.clone()),
-        rule_applied: rule_applied,
-        actual: actual_outcome,
+    print!("    {{ {}\"game\": {}, \"actual\": {}, \"rule_applied\": {}, \
+            \"adjudicated\": {}, \"correct\": {} }}",
+           file,
+           game_number,
+           json_game_stats(&outcome.actual),
+           json_rule_applied(&outcome.rule_applied),
+           json_game_stats(&outcome.adjudicated),
+           outcome.correctly_adjudicated());
+}
+
+fn print_json_files(files: &[String], summaries: &[RuleTestSummary]) {
+    println!("  \"files\": [");
+
+    for (index, (file, summary)) in files.iter().zip(summaries).enumerate() {
+        println!("    {{ \"file\": {}, \"games\": {}, \"adjudicated\": {}, \
+                  \"adjudicated_wrong\": {}, \"time_saved\": {}, \
+                  \"time_saved_perc\": {}, \"mse\": {} }}{}",
+                 json_string(file),
+                 summary.games,
+                 summary.num(),
+                 summary.num_wrong(),
+                 summary.time_saved(),
+                 json_f64(summary.time_saved_perc(), 4),
+                 json_f64(summary.mse(), 6),
+                 if index + 1 < files.len() { "," } else { "" });
     }
+
+    println!("  ],");
 }
 
-enum RuleType {
-    Resign,
-    Draw,
+fn json_color_stats(stats: &ColorStats) -> String {
+    format!("{{ \"games\": {}, \"resigned\": {}, \"resigned_wrong\": {}, \
+             \"drawn\": {}, \"drawn_wrong\": {}, \"time_saved\": {}, \
+             \"time_saved_perc\": {} }}",
+            stats.games,
+            stats.resigned,
+            stats.resigned_wrong,
+            stats.drawn,
+            stats.drawn_wrong,
+            stats.time_saved,
+            json_f64(stats.time_saved_perc(), 4))
 }
 
-struct AdjudicationOutcome {
-    actual: GameStats,
-    rule_applied: Option<RuleType>,
-    adjudicated: GameStats,
+fn print_json_engines(engines: &EngineSummaries) {
+    println!("  \"engines\": [");
+
+    for (index, engine) in engines.engines.iter().enumerate() {
+        let colors = &engine.colors;
+
+        println!("    {{ \"engine\": {}, \"white\": {}, \"black\": {}, \"total\": {} }}{}",
+                 json_string(&engine.name),
+                 json_color_stats(&colors[0]),
+                 json_color_stats(&colors[1]),
+                 json_color_stats(&colors[0].combine(&colors[1])),
+                 if index + 1 < engines.engines.len() { "," } else { "" });
+    }
+
+    println!("  ],");
 }
 
-impl AdjudicationOutcome {
-    fn correctly_adjudicated(&self) -> bool {
-        self.actual.score10 == self.adjudicated.score10
+// `summaries` has the summary with each of `TABLEBASE_SIZES`
+fn print_json_tablebase_savings(summaries: &[RuleTestSummary]) {
+    println!("  \"tablebase_savings\": [");
+
+    for (index, (max_pieces, summary)) in TABLEBASE_SIZES.iter().zip(summaries).enumerate() {
+        println!("    {{ \"max_pieces\": {}, \"adjudicated\": {}, \"time_saved\": {}, \
+                  \"time_saved_perc\": {}, \"total_time_saved\": {}, \
+                  \"total_time_saved_perc\": {} }}{}",
+                 max_pieces,
+                 summary.tablebase.num,
+                 summary.tablebase.time_saved,
+                 json_f64(summary.tablebase.time_saved_perc(summary.actual_time), 4),
+                 summary.time_saved(),
+                 json_f64(summary.time_saved_perc(), 4),
+                 if index + 1 < summaries.len() { "," } else { "" });
     }
 
-    fn time_saved(&self) -> u32 {
-        if self.correctly_adjudicated() {
-            (self.actual.time as i32 - self.adjudicated.time as i32) as u32
-        } else {
-            0
+    println!("  ],");
+}
+
+fn json_draw_claim_stats(stats: &DrawClaimStats, summary: &RuleTestSummary) -> String {
+    format!("{{ \"num\": {}, \"fifty_moves\": {}, \"repetition\": {}, \
+             \"insufficient_material\": {}, \"num_not_drawn\": {}, \"time_wasted\": {}, \
+             \"time_wasted_perc\": {} }}",
+            stats.num,
+            stats.fifty_moves,
+            stats.repetition,
+            stats.insufficient_material,
+            stats.num_not_drawn,
+            stats.time_wasted,
+            json_f64(stats.time_wasted_perc(summary.actual_time), 4))
+}
+
+fn print_json_summary(summary: &RuleTestSummary,
+                      with_tablebase: bool,
+                      draw_claims: Option<&DrawClaimStats>) {
+    println!("  \"games\": {},", summary.games);
+    println!("  \"adjudicated\": {},", summary.num());
+    println!("  \"adjudicated_wrong\": {},", summary.num_wrong());
+    println!("  \"both_rules\": {},", summary.both_rules);
+    println!("  \"untimed_games\": {},", summary.untimed_games);
+    println!("  \"total_time\": {},", summary.actual_time);
+    println!("  \"adjudicated_time\": {},", summary.adjudicated_time);
+    println!("  \"time_saved\": {},", summary.time_saved());
+    println!("  \"time_saved_perc\": {},", json_f64(summary.time_saved_perc(), 4));
+    println!("  \"mse\": {},", json_f64(summary.mse(), 6));
+    if let Some(draw_claims) = draw_claims {
+        println!("  \"draw_claims\": {},", json_draw_claim_stats(draw_claims, summary));
+    }
+    println!("  \"resign\": {},", json_rule_stats(&summary.resign, summary));
+    println!("  \"draw\": {}{}",
+             json_rule_stats(&summary.draw, summary),
+             if with_tablebase { "," } else { "" });
+    if with_tablebase {
+        println!("  \"tablebase\": {}", json_rule_stats(&summary.tablebase, summary));
+    }
+}
+
+fn print_text_outcome(game: &str, outcome: &AdjudicationOutcome) {
+    println!("{}, {}, {}, {}, {}, {}, {}, {}",
+             game,
+             outcome.actual.length,
+             outcome.actual.time,
+             outcome.actual.score10 as f32 / 10f32,
+             match outcome.rule_applied {
+                 Some(RuleType::Resign) => "R",
+                 Some(RuleType::Draw) => "D",
+                 Some(RuleType::Tablebase) => "T",
+                 None => "-",
+             },
+             outcome.adjudicated.length,
+             outcome.adjudicated.time,
+             outcome.adjudicated.score10 as f32 / 10f32);
+}
+
+// Adjudicates games as they're read so that the whole file never
+// needs to be in memory. Only the outcomes are kept, and nothing is
+// printed until all the games are done so that an invalid game
+// can't leave the output half written. `games` yields the index in
+// `files` of the file each game came from and the game's number in
+// that file.
+fn test_rule<I>(games: I,
+                files: &[String],
+                resign_rule: &ResignRule,
+                draw_rule: &DrawRule,
+                tablebase_rule: Option<&TablebaseRule>,
+                priority: RulePriority,
+                report: &Report)
+    where I: Iterator<Item = (usize, u32, GameData)> {
+
+    let no_tablebase_rule = TablebaseRule::none();
+
+    let mut summary = RuleTestSummary::default();
+    let mut file_summaries = vec![RuleTestSummary::default(); files.len()];
+    let mut engine_summaries = EngineSummaries::default();
+    let mut tablebase_summaries = vec![RuleTestSummary::default(); TABLEBASE_SIZES.len()];
+    let mut draw_claims = DrawClaimStats::default();
+    let mut outcomes = Vec::new();
+    let name_files = files.len() > 1;
+
+    for (file_index, game_number, game) in games {
+        let outcome = adjudicate_game_with(
+            &game, resign_rule, draw_rule, tablebase_rule.unwrap_or(&no_tablebase_rule), priority);
+        summary.add(&outcome);
+        file_summaries[file_index].add(&outcome);
+        engine_summaries.add(&game, &outcome);
+
+        if report.draw_claims {
+            draw_claims.add(&game);
+        }
+
+        if report.tablebase_savings {
+            for (max_pieces, summary) in TABLEBASE_SIZES.iter().zip(&mut tablebase_summaries) {
+                let tablebase_rule = TablebaseRule::new(*max_pieces).unwrap();
+                summary.add(&adjudicate_game_with(
+                    &game, resign_rule, draw_rule, &tablebase_rule, priority));
+            }
+        }
+
+        outcomes.push((file_index, game_number, outcome));
+    }
+
+    match report.format {
+        OutputFormat::Text => {
+            if report.verbose {
+                println!("game, actual_length, actual_time, actual_score, \
+                          rule_applied, adjudicated_length, adjudicated_time, adjudicated_score");
+                for &(file_index, game_number, ref outcome) in &outcomes {
+                    let game = if name_files {
+                        format!("{}:{}", files[file_index], game_number)
+                    } else {
+                        game_number.to_string()
+                    };
+                    print_text_outcome(&game, outcome);
+                }
+                println!("");
+            }
+            print_text_summary(&summary,
+                               tablebase_rule.is_some(),
+                               priority,
+                               if report.draw_claims { Some(&draw_claims) } else { None });
+            if report.per_file {
+                println!("");
+                print_text_files(files, &file_summaries);
+            }
+            if report.per_engine {
+                println!("");
+                print_text_engines(&engine_summaries);
+            }
+            if report.tablebase_savings {
+                println!("");
+                print_text_tablebase_savings(&tablebase_summaries);
+            }
+        },
+        OutputFormat::Json => {
+            println!("{{");
+            println!("  \"outcomes\": [");
+            for (index, &(file_index, game_number, ref outcome)) in outcomes.iter().enumerate() {
+                let file = if name_files { Some(files[file_index].as_str()) } else { None };
+                print_json_outcome(index, file, game_number, outcome);
+            }
+            if !outcomes.is_empty() {
+                println!("");
+            }
+            println!("  ],");
+            if report.per_file {
+                print_json_files(files, &file_summaries);
+            }
+            if report.per_engine {
+                print_json_engines(&engine_summaries);
+            }
+            if report.tablebase_savings {
+                print_json_tablebase_savings(&tablebase_summaries);
+            }
+            print_json_summary(&summary,
+                               tablebase_rule.is_some(),
+                               if report.draw_claims { Some(&draw_claims) } else { None });
+            println!("}}");
+        },
+    }
+}
+
+// The tablebase rule is only shown if it was used. It's never
+// wrong so it has no MSE. The games where both the resign and draw
+// rules applied are only shown if there were any.
+fn print_text_summary(summary: &RuleTestSummary,
+                      with_tablebase: bool,
+                      priority: RulePriority,
+                      draw_claims: Option<&DrawClaimStats>) {
+
+    let actual_time = summary.actual_time;
+    let resign = &summary.resign;
+    let draw = &summary.draw;
+    let tablebase = &summary.tablebase;
+
+    println!("Games: {}", summary.games);
+    println!("Adjudicated: {} ({} wrong)", summary.num(), summary.num_wrong());
+    println!("  Resign: {} ({} wrong)", resign.num, resign.num_wrong);
+    println!("  Draw: {} ({} wrong)", draw.num, draw.num_wrong);
+    if with_tablebase {
+        println!("  Tablebase: {}", tablebase.num);
+    }
+    if summary.both_rules > 0 {
+        println!("  Resign and draw on the same ply: {} (scored as {})",
+                 summary.both_rules,
+                 match priority {
+                     RulePriority::Resign => "resign",
+                     RulePriority::Draw => "draw",
+                 });
+    }
+    println!("");
+
+    println!("Total Time: {}", format_time(actual_time));
+    println!("After Adjudication: {}", format_time(summary.adjudicated_time));
+    println!("Time saved: {} ({:.2}%)", format_time(summary.time_saved()), summary.time_saved_perc());
+    println!("  Resign: {} ({:.2}%)", format_time(resign.time_saved), resign.time_saved_perc(actual_time));
+    println!("  Draw: {} ({:.2}%)", format_time(draw.time_saved), draw.time_saved_perc(actual_time));
+    if with_tablebase {
+        println!("  Tablebase: {} ({:.2}%)",
+                 format_time(tablebase.time_saved),
+                 tablebase.time_saved_perc(actual_time));
+    }
+    println!("Note: 'Time saved' excludes incorrectly adjudicated games");
+    if summary.untimed_games > 0 {
+        println!("Note: Times exclude games with a move of unknown time: {}",
+                 summary.untimed_games);
+    }
+    println!("");
+
+    if let Some(draw_claims) = draw_claims {
+        println!("Claimable draws: {} ({} not drawn)", draw_claims.num, draw_claims.num_not_drawn);
+        println!("  Fifty moves: {}", draw_claims.fifty_moves);
+        println!("  Repetition: {}", draw_claims.repetition);
+        println!("  Insufficient material: {}", draw_claims.insufficient_material);
+        println!("Time after claims: {} ({:.2}%)",
+                 format_time(draw_claims.time_wasted),
+                 draw_claims.time_wasted_perc(actual_time));
+        println!("");
+    }
+
+    let mse = summary.mse();
+
+    println!("Mean Squared Error: {:.6}", mse);
+    println!("  Resign: {:.6}", resign.mse(summary.games));
+    println!("  Draw: {:.6}", draw.mse(summary.games));
+    println!("Root MSE: {:.3}", mse.powf(0.5));
+}
+
+fn print_text_files(files: &[String], summaries: &[RuleTestSummary]) {
+    let width = files.iter()
+        .map(|file| file.len())
+        .fold(16, max);
+
+    println!("{:<width$} {:>6} {:>11} {:>6} {:>22} {:>9}",
+             "File", "Games", "Adjudicated", "Wrong", "Time saved", "MSE",
+             width = width);
+
+    for (file, summary) in files.iter().zip(summaries) {
+        println!("{:<width$} {:>6} {:>11} {:>6} {:>22} {:>9.6}",
+                 file,
+                 summary.games,
+                 summary.num(),
+                 summary.num_wrong(),
+                 format!("{} ({:.2}%)",
+                         format_time(summary.time_saved()),
+                         summary.time_saved_perc()),
+                 summary.mse(),
+                 width = width);
+    }
+}
+
+fn print_text_engines(engines: &EngineSummaries) {
+    let width = engines.engines.iter()
+        .map(|engine| engine.name.len())
+        .fold(16, max);
+
+    println!("{:<width$} {:<5} {:>6} {:>8} {:>6} {:>6} {:>6} {:>22}",
+             "Engine", "Color", "Games", "Resigned", "Wrong", "Drawn", "Wrong", "Time saved",
+             width = width);
+
+    for engine in &engines.engines {
+        let colors = &engine.colors;
+        let total = colors[0].combine(&colors[1]);
+
+        for (color, stats) in ["White", "Black", "Total"].iter().zip(&[&colors[0], &colors[1], &total]) {
+            // There's no percentage for a color the engine never played
+            let time_saved = if stats.games > 0 {
+                format!("{} ({:.2}%)", format_time(stats.time_saved), stats.time_saved_perc())
+            } else {
+                "-".to_string()
+            };
+
+            println!("{:<width$} {:<5} {:>6} {:>8} {:>6} {:>6} {:>6} {:>22}",
+                     engine.name,
+                     color,
+                     stats.games,
+                     stats.resigned,
+                     stats.resigned_wrong,
+                     stats.drawn,
+                     stats.drawn_wrong,
+                     time_saved,
+                     width = width);
         }
     }
+}
+
+// `summaries` has the summary with each of `TABLEBASE_SIZES`
+fn print_text_tablebase_savings(summaries: &[RuleTestSummary]) {
+    println!("{:<6} {:>11} {:>22} {:>22}",
+             "Pieces", "Adjudicated", "Time saved", "Total time saved");
+
+    for (max_pieces, summary) in TABLEBASE_SIZES.iter().zip(summaries) {
+        println!("{:<6} {:>11} {:>22} {:>22}",
+                 max_pieces,
+                 summary.tablebase.num,
+                 format!("{} ({:.2}%)",
+                         format_time(summary.tablebase.time_saved),
+                         summary.tablebase.time_saved_perc(summary.actual_time)),
+                 format!("{} ({:.2}%)",
+                         format_time(summary.time_saved()),
+                         summary.time_saved_perc()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_time;
 
-    fn squared_error10(&self) -> u32 {
-       (self.actual.score10 as i32 - self.adjudicated.score10 as i32).pow(2) as u32
+    #[test]
+    fn time_formatting() {
+        assert_eq!(format_time(3723004), "1:02:03.004");
+        // A million games of 20 seconds each
+        assert_eq!(format_time(20_000_000_000), "5555:33:20.000");
     }
 }
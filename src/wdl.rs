@@ -0,0 +1,105 @@
+use game_data::Eval;
+
+// The chances of a win, draw and loss for the side an eval is from,
+// which add up to 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Wdl {
+    pub win: f64,
+    pub draw: f64,
+    pub loss: f64,
+}
+
+// A logistic model that turns an eval into the chances of a win, draw
+// and loss, like the one Stockfish uses for `UCI_ShowWDL`. What an
+// eval means changes over a game, so the curve depends on either the
+// ply or the material left.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WdlModel {
+    Ply,
+    Material,
+}
+
+// The coefficients of the cubic polynomials that give the model's
+// parameters, in Stockfish's internal units. A 100 centipawn eval is
+// a 50% win at ply 64 in the ply model and with 58 material in the
+// material model, which is what `*_PAWN_VALUE` is.
+const PLY_AS: [f64; 4] = [-0.58270499, 2.68512549, 15.24638015, 344.49745382];
+const PLY_BS: [f64; 4] = [-2.65734562, 15.96509799, -20.69040836, 73.61029937];
+const PLY_PAWN_VALUE: f64 = 361.0;
+
+const MATERIAL_AS: [f64; 4] = [-150.77043883, 394.96159472, -321.73403766, 406.15850091];
+const MATERIAL_BS: [f64; 4] = [62.33245393, -91.02264855, 45.88486850, 51.63461272];
+const MATERIAL_PAWN_VALUE: f64 = 328.0;
+
+impl WdlModel {
+    // Returns the chances for `eval`, which an engine gave when
+    // searching a position with `material` left, counted as in
+    // `Position`, at the 0-based `ply` of the game
+    pub fn wdl(self, eval: Eval, ply: u32, material: u32) -> Wdl {
+        let (a, b) = self.parameters(ply, material);
+
+        let x = eval.centipawns().clamp(-4000, 4000) as f64;
+        let win = 1f64 / (1f64 + ((a - x) / b).exp());
+        let loss = 1f64 / (1f64 + ((a + x) / b).exp());
+
+        Wdl {
+            win: win,
+            draw: 1f64 - win - loss,
+            loss: loss,
+        }
+    }
+
+    // Returns the eval in centipawns that's a 50% win and how
+    // spread out the curve is
+    fn parameters(self, ply: u32, material: u32) -> (f64, f64) {
+        let (as_, bs, m, pawn_value) = match self {
+            WdlModel::Ply =>
+                (&PLY_AS, &PLY_BS, ply.min(240) as f64 / 64f64, PLY_PAWN_VALUE),
+            WdlModel::Material =>
+                (&MATERIAL_AS, &MATERIAL_BS, material.clamp(17, 78) as f64 / 58f64,
+                 MATERIAL_PAWN_VALUE),
+        };
+
+        let polynomial = |coefficients: &[f64; 4]| {
+            coefficients.iter().fold(0f64, |result, coefficient| result * m + coefficient)
+        };
+
+        (100f64 * polynomial(as_) / pawn_value, 100f64 * polynomial(bs) / pawn_value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game_data::Eval;
+
+    use super::WdlModel;
+
+    #[test]
+    fn wdl_models() {
+        // The material at the start, and once the queens and a pawn
+        // each are gone
+        let start = 78;
+        let no_queens = 58;
+
+        let wdl = WdlModel::Ply.wdl(Eval::Centipawns(100), 64, start);
+        assert!((wdl.win - 0.5).abs() < 0.01);
+        let wdl = WdlModel::Material.wdl(Eval::Centipawns(100), 0, no_queens);
+        assert!((wdl.win - 0.5).abs() < 0.01);
+
+        for model in &[WdlModel::Ply, WdlModel::Material] {
+            let wdl = model.wdl(Eval::Centipawns(0), 40, start);
+            assert!((wdl.win - wdl.loss).abs() < 1e-9);
+            assert!(wdl.draw > 0.9);
+
+            let wdl = model.wdl(Eval::Centipawns(-300), 40, start);
+            assert!(wdl.win < 0.01 && wdl.loss > 0.5);
+            assert!((wdl.win + wdl.draw + wdl.loss - 1f64).abs() < 1e-9);
+
+            assert!(model.wdl(Eval::Mate(-3), 40, start).loss > 0.999);
+        }
+
+        let early = WdlModel::Ply.wdl(Eval::Centipawns(150), 10, start);
+        let late = WdlModel::Ply.wdl(Eval::Centipawns(150), 120, start);
+        assert!(late.win < early.win);
+    }
+}
@@ -24,28 +24,48 @@ struct RunOutput {
 fn run_test(sample_file: &str, resign_rule: &str, draw_rule: &str, verbose: bool)
     -> Result<RunOutput>
 {
-    let mut exe = Command::new(get_exe_path());
-    let base = exe.arg("test")
+    let mut command = Command::new(get_exe_path());
+    command.arg("test")
         .arg(get_sample_path(sample_file))
         .arg(resign_rule)
         .arg(draw_rule);
 
-    let command = if verbose {
-        base.arg("--verbose")
-    } else {
-        base
-    };
+    if verbose {
+        command.arg("--verbose");
+    }
+
+    run(&mut command)
+}
+
+fn run_command(command: &str, sample_file: &str, args: &[&str])
+    -> Result<RunOutput>
+{
+    run(Command::new(get_exe_path())
+        .arg(command)
+        .arg(get_sample_path(sample_file))
+        .args(args))
+}
 
-    let result = command.output();
+// Runs cara in the sample directory so that file names in the output
+// don't depend on where the repository is
+fn run_in_input_dir(args: &[&str]) -> Result<RunOutput> {
+    let mut input_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    input_dir.push("tests/input");
 
-    match result {
+    run(Command::new(get_exe_path())
+        .current_dir(input_dir)
+        .args(args))
+}
+
+// Runs `command` and keeps what it printed as text
+fn run(command: &mut Command) -> Result<RunOutput> {
+    match command.output() {
         Ok(output) => Ok(RunOutput {
             stdout: String::from_utf8_lossy(&output.stdout).to_string(),
             stderr: String::from_utf8_lossy(&output.stderr).to_string(),
             status: output.status }),
         Err(error) => Err(error),
     }
-
 }
 
 #[test]
@@ -102,7 +122,6 @@ fn test_command_no_rules() {
     );
 }
 
-
 #[test]
 fn test_command_resign_rule() {
     let output = run_test("resign.pgn", "250/3", "none", true).unwrap();
@@ -137,6 +156,107 @@ fn test_command_resign_rule() {
     );
 }
 
+#[test]
+fn test_command_mate_resign_rule() {
+    let output = run_test("resign.pgn", "M5/2", "none", true).unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "game, actual_length, actual_time, actual_score, ",
+            "rule_applied, adjudicated_length, adjudicated_time, adjudicated_score\n",
+            "1, 73, 16590, 0.5, -, 73, 16590, 0.5\n",
+            "2, 159, 22520, 1, R, 156, 22516, 1\n",
+            "3, 160, 22432, 0, R, 153, 22370, 0\n",
+            "4, 141, 22478, 0.5, -, 141, 22478, 0.5\n",
+            "5, 512, 33966, 0.5, -, 512, 33966, 0.5\n",
+            "\n",
+            "Games: 5\n",
+            "Adjudicated: 2 (0 wrong)\n",
+            "  Resign: 2 (0 wrong)\n",
+            "  Draw: 0 (0 wrong)\n",
+            "\n",
+            "Total Time: 0:01:57.986\n",
+            "After Adjudication: 0:01:57.920\n",
+            "Time saved: 0:00:00.066 (0.06%)\n",
+            "  Resign: 0:00:00.066 (0.06%)\n",
+            "  Draw: 0:00:00.000 (0.00%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.000000\n",
+            "  Resign: 0.000000\n",
+            "  Draw: 0.000000\n",
+            "Root MSE: 0.000\n")
+    );
+}
+
+#[test]
+fn test_command_resign_rule_min_depth() {
+    let output = run_test("resign.pgn", "250/3,depth=20", "none", true).unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "game, actual_length, actual_time, actual_score, ",
+            "rule_applied, adjudicated_length, adjudicated_time, adjudicated_score\n",
+            "1, 73, 16590, 0.5, -, 73, 16590, 0.5\n",
+            "2, 159, 22520, 1, R, 140, 22210, 1\n",
+            "3, 160, 22432, 0, R, 155, 22417, 0\n",
+            "4, 141, 22478, 0.5, -, 141, 22478, 0.5\n",
+            "5, 512, 33966, 0.5, R, 110, 19640, 1\n",
+            "\n",
+            "Games: 5\n",
+            "Adjudicated: 3 (1 wrong)\n",
+            "  Resign: 3 (1 wrong)\n",
+            "  Draw: 0 (0 wrong)\n",
+            "\n",
+            "Total Time: 0:01:57.986\n",
+            "After Adjudication: 0:01:43.335\n",
+            "Time saved: 0:00:00.325 (0.28%)\n",
+            "  Resign: 0:00:00.325 (0.28%)\n",
+            "  Draw: 0:00:00.000 (0.00%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.050000\n",
+            "  Resign: 0.050000\n",
+            "  Draw: 0.000000\n",
+            "Root MSE: 0.224\n")
+    );
+}
+
+#[test]
+fn test_command_two_sided_resign_rule() {
+    let output = run_test("resign.pgn", "250/1,twosided", "none", true).unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "game, actual_length, actual_time, actual_score, ",
+            "rule_applied, adjudicated_length, adjudicated_time, adjudicated_score\n",
+            "1, 73, 16590, 0.5, -, 73, 16590, 0.5\n",
+            "2, 159, 22520, 1, R, 116, 20982, 1\n",
+            "3, 160, 22432, 0, R, 119, 20831, 0\n",
+            "4, 141, 22478, 0.5, R, 121, 21802, 0\n",
+            "5, 512, 33966, 0.5, R, 110, 19640, 1\n",
+            "\n",
+            "Games: 5\n",
+            "Adjudicated: 4 (2 wrong)\n",
+            "  Resign: 4 (2 wrong)\n",
+            "  Draw: 0 (0 wrong)\n",
+            "\n",
+            "Total Time: 0:01:57.986\n",
+            "After Adjudication: 0:01:39.845\n",
+            "Time saved: 0:00:03.139 (2.66%)\n",
+            "  Resign: 0:00:03.139 (2.66%)\n",
+            "  Draw: 0:00:00.000 (0.00%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.100000\n",
+            "  Resign: 0.100000\n",
+            "  Draw: 0.000000\n",
+            "Root MSE: 0.316\n")
+    );
+}
 
 #[test]
 fn test_command_draw_rule() {
@@ -171,3 +291,784 @@ fn test_command_draw_rule() {
             "Root MSE: 0.316\n")
     );
 }
+
+#[test]
+fn resign_command() {
+    let output = run_command(
+        "resign", "resign.pgn",
+        &["--evals", "200:300:50", "--counts", "2:4:1", "--max-wrong", "1", "--top", "3"])
+        .unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "Games: 5\n",
+            "Rules tested: 9\n",
+            "Rules within error budget (max 1 wrong): 4\n",
+            "\n",
+            "Rule             Adjudicated  Wrong             Time saved       MSE\n",
+            "200/4                      3      1    0:00:03.869 (3.28%)  0.050000\n",
+            "250/4                      3      1    0:00:02.710 (2.30%)  0.050000\n",
+            "300/3                      3      1    0:00:02.173 (1.84%)  0.050000\n")
+    );
+}
+
+#[test]
+fn resign_command_nothing_within_budget() {
+    let output = run_command(
+        "resign", "resign.pgn",
+        &["--evals", "200", "--counts", "2"])
+        .unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "Games: 5\n",
+            "Rules tested: 1\n",
+            "Rules within error budget (max 0 wrong): 0\n",
+            "\n",
+            "No rule is within the error budget.\n")
+    );
+}
+
+#[test]
+fn resign_command_bad_range() {
+    let output = run_command("resign", "resign.pgn", &["--evals", "300:200:50"]).unwrap();
+
+    assert_eq!(output.stdout, "".to_string());
+    assert_eq!(output.stderr, "error: --evals has bad format\n".to_string());
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn draw_command() {
+    let output = run_command(
+        "draw", "draw.pgn",
+        &["--from-moves", "30:40:5", "--evals", "20:30:10", "--counts", "6:8:2",
+          "--max-wrong", "1", "--top", "3"])
+        .unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "Games: 5\n",
+            "Rules tested: 12\n",
+            "Rules within error budget (max 1 wrong): 8\n",
+            "\n",
+            "Rule             Adjudicated  Wrong             Time saved       MSE\n",
+            "30:20/6                    2      1    0:00:00.951 (1.05%)  0.050000\n",
+            "30:20/8                    2      1    0:00:00.453 (0.50%)  0.050000\n",
+            "35:20/6                    2      1    0:00:00.261 (0.29%)  0.050000\n")
+    );
+}
+
+#[test]
+fn draw_command_zero_eval() {
+    let output = run_command("draw", "draw.pgn", &["--evals", "0:20:10"]).unwrap();
+
+    assert_eq!(output.stdout, "".to_string());
+    assert_eq!(output.stderr, "error: Draw rule evaluation must be positive\n".to_string());
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn optimize_command() {
+    let output = run_command(
+        "optimize", "resign.pgn",
+        &["--resign-evals", "200:400:100", "--resign-counts", "3",
+          "--draw-from-moves", "30", "--draw-evals", "10:30:20", "--draw-counts", "8",
+          "--max-wrong", "1", "--top", "3"])
+        .unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "Games: 5\n",
+            "Rule pairs tested: 6\n",
+            "Objective: most time saved\n",
+            "  Max wrong: 1\n",
+            "Rule pairs meeting the constraints: 4\n",
+            "\n",
+            "Resign/Draw rules Adjudicated  Wrong             Time saved       MSE\n",
+            "400/3 30:30/8               4      0    0:00:02.740 (2.32%)  0.000000\n",
+            "300/3 30:30/8               5      1    0:00:02.740 (2.32%)  0.050000\n",
+            "400/3 30:10/8               4      0    0:00:02.626 (2.23%)  0.000000\n")
+    );
+}
+
+#[test]
+fn optimize_command_mse_objective() {
+    let output = run_command(
+        "optimize", "resign.pgn",
+        &["--resign-evals", "200:300:100", "--draw-evals", "10", "--draw-counts", "8",
+          "--objective", "mse", "--min-time-saved", "3", "--top", "3"])
+        .unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "Games: 5\n",
+            "Rule pairs tested: 32\n",
+            "Objective: lowest MSE\n",
+            "  Min time saved: 3.00%\n",
+            "Rule pairs meeting the constraints: 14\n",
+            "\n",
+            "Resign/Draw rules Adjudicated  Wrong             Time saved       MSE\n",
+            "200/4 20:10/8               5      1    0:00:06.683 (5.66%)  0.050000\n",
+            "200/6 20:10/8               5      1    0:00:06.152 (5.21%)  0.050000\n",
+            "200/8 20:10/8               5      1    0:00:05.551 (4.70%)  0.050000\n")
+    );
+}
+
+#[test]
+fn frontier_command() {
+    let output = run_command(
+        "frontier", "resign.pgn",
+        &["--resign-evals", "400:500:100", "--resign-counts", "2:4:2",
+          "--draw-evals", "10:50:40", "--draw-counts", "4"])
+        .unwrap();
+
+    // Rule pairs with the same results are all listed
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "Games: 5\n",
+            "Rule pairs tested: 32\n",
+            "Rule pairs on the time saved/wrong frontier: 5\n",
+            "\n",
+            "Resign/Draw rules Adjudicated  Wrong             Time saved       MSE\n",
+            "400/2 20:10/4               5      0    0:00:07.213 (6.11%)  0.000000\n",
+            "400/2 20:50/4               5      2   0:00:18.448 (15.64%)  0.100000\n",
+            "400/4 20:50/4               5      2   0:00:18.448 (15.64%)  0.100000\n",
+            "500/2 20:50/4               5      2   0:00:18.448 (15.64%)  0.100000\n",
+            "500/4 20:50/4               5      2   0:00:18.448 (15.64%)  0.100000\n")
+    );
+}
+
+#[test]
+fn frontier_command_csv() {
+    let output = run_command(
+        "frontier", "resign.pgn",
+        &["--resign-counts", "3", "--draw-evals", "10:30:20", "--draw-counts", "8",
+          "--error", "mse", "--format", "csv"])
+        .unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "resign_rule, draw_rule, adjudicated, wrong, time_saved, time_saved_perc, mse\n",
+            "400/3, 20:10/8, 4, 0, 4987, 4.2268, 0.000000\n",
+            "200/3, 20:10/8, 5, 2, 6949, 5.8897, 0.100000\n")
+    );
+}
+
+#[test]
+fn test_command_json_format() {
+    let output = run_command(
+        "test", "draw.pgn", &["none", "34:30/8", "--format", "json"]).unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "{\n",
+            "  \"outcomes\": [\n",
+            "    { \"game\": 1, \"actual\": { \"length\": 55, \"time\": 11406, \"score\": 0.5 }, \"rule_applied\": null, \"adjudicated\": { \"length\": 55, \"time\": 11406, \"score\": 0.5 }, \"correct\": true },\n",
+            "    { \"game\": 2, \"actual\": { \"length\": 73, \"time\": 16862, \"score\": 0.5 }, \"rule_applied\": null, \"adjudicated\": { \"length\": 73, \"time\": 16862, \"score\": 0.5 }, \"correct\": true },\n",
+            "    { \"game\": 3, \"actual\": { \"length\": 73, \"time\": 16590, \"score\": 0.5 }, \"rule_applied\": \"draw\", \"adjudicated\": { \"length\": 68, \"time\": 16220, \"score\": 0.5 }, \"correct\": true },\n",
+            "    { \"game\": 4, \"actual\": { \"length\": 151, \"time\": 22138, \"score\": 1 }, \"rule_applied\": \"draw\", \"adjudicated\": { \"length\": 68, \"time\": 15652, \"score\": 0.5 }, \"correct\": false },\n",
+            "    { \"game\": 5, \"actual\": { \"length\": 190, \"time\": 23480, \"score\": 0 }, \"rule_applied\": \"draw\", \"adjudicated\": { \"length\": 94, \"time\": 18851, \"score\": 0.5 }, \"correct\": false }\n",
+            "  ],\n",
+            "  \"games\": 5,\n",
+            "  \"adjudicated\": 3,\n",
+            "  \"adjudicated_wrong\": 2,\n",
+            "  \"both_rules\": 0,\n",
+            "  \"untimed_games\": 0,\n",
+            "  \"total_time\": 90476,\n",
+            "  \"adjudicated_time\": 78991,\n",
+            "  \"time_saved\": 370,\n",
+            "  \"time_saved_perc\": 0.4089,\n",
+            "  \"mse\": 0.100000,\n",
+            "  \"resign\": { \"num\": 0, \"num_wrong\": 0, \"time_saved\": 0, \"time_saved_perc\": 0.0000, \"mse\": 0.000000 },\n",
+            "  \"draw\": { \"num\": 3, \"num_wrong\": 2, \"time_saved\": 370, \"time_saved_perc\": 0.4089, \"mse\": 0.100000 }\n",
+            "}\n")
+    );
+}
+
+#[test]
+fn test_command_per_file() {
+    let output = run_in_input_dir(&["test", "[dr]*.pgn", "250/3", "34:30/8", "--per-file"]).unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "Games: 10\n",
+            "Adjudicated: 8 (4 wrong)\n",
+            "  Resign: 4 (2 wrong)\n",
+            "  Draw: 4 (2 wrong)\n",
+            "\n",
+            "Total Time: 0:03:28.462\n",
+            "After Adjudication: 0:02:58.617\n",
+            "Time saved: 0:00:03.814 (1.83%)\n",
+            "  Resign: 0:00:03.074 (1.47%)\n",
+            "  Draw: 0:00:00.740 (0.35%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.100000\n",
+            "  Resign: 0.050000\n",
+            "  Draw: 0.050000\n",
+            "Root MSE: 0.316\n",
+            "\n",
+            "File              Games Adjudicated  Wrong             Time saved       MSE\n",
+            "draw.pgn              5           3      2    0:00:00.370 (0.41%)  0.100000\n",
+            "resign.pgn            5           5      2    0:00:03.444 (2.92%)  0.100000\n")
+    );
+}
+
+#[test]
+fn test_command_missing_rules() {
+    let output = run_in_input_dir(&["test", "draw.pgn", "resign.pgn"]).unwrap();
+
+    assert_eq!(output.stdout, "".to_string());
+    assert_eq!(output.stderr, "error: Missing <resign_rule> and <draw_rule>\n".to_string());
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn resign_command_multiple_files() {
+    let output = run_in_input_dir(
+        &["resign", "draw.pgn", "resign.pgn", "--evals", "400:450:50", "--counts", "1:2:1"])
+        .unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "Games: 10\n",
+            "Rules tested: 4\n",
+            "Rules within error budget (max 0 wrong): 4\n",
+            "\n",
+            "Rule             Adjudicated  Wrong             Time saved       MSE\n",
+            "400/1                      4      0    0:00:04.277 (2.05%)  0.000000\n",
+            "400/2                      4      0    0:00:04.003 (1.92%)  0.000000\n",
+            "450/1                      4      0    0:00:03.826 (1.84%)  0.000000\n",
+            "450/2                      4      0    0:00:03.444 (1.65%)  0.000000\n")
+    );
+}
+
+#[test]
+fn resign_command_missing_second_file() {
+    let output = run_in_input_dir(&["resign", "resign.pgn", "missing.pgn"]).unwrap();
+
+    assert_eq!(output.stdout, "".to_string());
+    assert_eq!(output.stderr, "error: missing.pgn: Can't open file\n".to_string());
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn resign_command_no_matching_files() {
+    let output = run_in_input_dir(&["resign", "*.cbv"]).unwrap();
+
+    assert_eq!(output.stdout, "".to_string());
+    assert_eq!(output.stderr, "error: No PGN files found for '*.cbv'\n".to_string());
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_command_lichess_format() {
+    let output = run_command(
+        "test", "lichess.pgn", &["250/1", "2:20/2", "--verbose", "--comment-format", "lichess"])
+        .unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "game, actual_length, actual_time, actual_score, ",
+            "rule_applied, adjudicated_length, adjudicated_time, adjudicated_score\n",
+            "1, 7, 25000, 1, R, 6, 22000, 1\n",
+            "2, 4, 9000, 0, R, 3, 6000, 0\n",
+            "3, 8, 35000, 0.5, D, 4, 12000, 0.5\n",
+            "\n",
+            "Games: 3\n",
+            "Adjudicated: 3 (0 wrong)\n",
+            "  Resign: 2 (0 wrong)\n",
+            "  Draw: 1 (0 wrong)\n",
+            "\n",
+            "Total Time: 0:01:09.000\n",
+            "After Adjudication: 0:00:40.000\n",
+            "Time saved: 0:00:29.000 (42.03%)\n",
+            "  Resign: 0:00:06.000 (8.70%)\n",
+            "  Draw: 0:00:23.000 (33.33%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.000000\n",
+            "  Resign: 0.000000\n",
+            "  Draw: 0.000000\n",
+            "Root MSE: 0.000\n")
+    );
+}
+
+#[test]
+fn test_command_book_moves_and_remarks() {
+    let output = run_test("book.pgn", "250/2", "none", true).unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "game, actual_length, actual_time, actual_score, ",
+            "rule_applied, adjudicated_length, adjudicated_time, adjudicated_score\n",
+            "1, 8, 1030, 1, R, 6, 530, 1\n",
+            "2, 6, 450, 1, -, 6, 450, 1\n",
+            "\n",
+            "Games: 2\n",
+            "Adjudicated: 1 (0 wrong)\n",
+            "  Resign: 1 (0 wrong)\n",
+            "  Draw: 0 (0 wrong)\n",
+            "\n",
+            "Total Time: 0:00:01.030\n",
+            "After Adjudication: 0:00:00.530\n",
+            "Time saved: 0:00:00.500 (48.54%)\n",
+            "  Resign: 0:00:00.500 (48.54%)\n",
+            "  Draw: 0:00:00.000 (0.00%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "Note: Times exclude games with a move of unknown time: 1\n",
+            "\n",
+            "Mean Squared Error: 0.000000\n",
+            "  Resign: 0.000000\n",
+            "  Draw: 0.000000\n",
+            "Root MSE: 0.000\n")
+    );
+}
+
+#[test]
+fn test_command_tag_filters() {
+    let output = run_command(
+        "test", "draw.pgn",
+        &["none", "30:10/3", "--verbose", "--white", "stockfish1", "--tag", "Date=^2015"])
+        .unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "game, actual_length, actual_time, actual_score, ",
+            "rule_applied, adjudicated_length, adjudicated_time, adjudicated_score\n",
+            "1, 55, 11406, 0.5, -, 55, 11406, 0.5\n",
+            "3, 73, 16590, 0.5, D, 60, 15283, 0.5\n",
+            "\n",
+            "Games: 2\n",
+            "Adjudicated: 1 (0 wrong)\n",
+            "  Resign: 0 (0 wrong)\n",
+            "  Draw: 1 (0 wrong)\n",
+            "\n",
+            "Total Time: 0:00:27.996\n",
+            "After Adjudication: 0:00:26.689\n",
+            "Time saved: 0:00:01.307 (4.67%)\n",
+            "  Resign: 0:00:00.000 (0.00%)\n",
+            "  Draw: 0:00:01.307 (4.67%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.000000\n",
+            "  Resign: 0.000000\n",
+            "  Draw: 0.000000\n",
+            "Root MSE: 0.000\n")
+    );
+}
+
+#[test]
+fn test_command_bad_tag_filter() {
+    let output = run_command("test", "draw.pgn", &["none", "none", "--tag", "Round"]).unwrap();
+
+    assert_eq!(output.stdout, "".to_string());
+    assert_eq!(output.stderr, "error: Bad tag filter 'Round', expected <key>=<regex>\n".to_string());
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_command_per_engine() {
+    let output = run_command("test", "resign.pgn", &["500/3", "none", "--per-engine"]).unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "Games: 5\n",
+            "Adjudicated: 2 (0 wrong)\n",
+            "  Resign: 2 (0 wrong)\n",
+            "  Draw: 0 (0 wrong)\n",
+            "\n",
+            "Total Time: 0:01:57.986\n",
+            "After Adjudication: 0:01:56.522\n",
+            "Time saved: 0:00:01.464 (1.24%)\n",
+            "  Resign: 0:00:01.464 (1.24%)\n",
+            "  Draw: 0:00:00.000 (0.00%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.000000\n",
+            "  Resign: 0.000000\n",
+            "  Draw: 0.000000\n",
+            "Root MSE: 0.000\n",
+            "\n",
+            "Engine           Color  Games Resigned  Wrong  Drawn  Wrong             Time saved\n",
+            "stockfish1       White      4        1      0      0      0    0:00:01.464 (1.53%)\n",
+            "stockfish1       Black      1        0      0      0      0    0:00:00.000 (0.00%)\n",
+            "stockfish1       Total      5        1      0      0      0    0:00:01.464 (1.24%)\n",
+            "stockfish2       White      1        0      0      0      0    0:00:00.000 (0.00%)\n",
+            "stockfish2       Black      4        1      0      0      0    0:00:01.464 (1.53%)\n",
+            "stockfish2       Total      5        1      0      0      0    0:00:01.464 (1.24%)\n")
+    );
+
+    // Each engine only played one color in the games left
+    let output = run_command(
+        "test", "resign.pgn",
+        &["500/3", "none", "--per-engine", "--white", "stockfish1"])
+        .unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "Games: 4\n",
+            "Adjudicated: 2 (0 wrong)\n",
+            "  Resign: 2 (0 wrong)\n",
+            "  Draw: 0 (0 wrong)\n",
+            "\n",
+            "Total Time: 0:01:35.508\n",
+            "After Adjudication: 0:01:34.044\n",
+            "Time saved: 0:00:01.464 (1.53%)\n",
+            "  Resign: 0:00:01.464 (1.53%)\n",
+            "  Draw: 0:00:00.000 (0.00%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.000000\n",
+            "  Resign: 0.000000\n",
+            "  Draw: 0.000000\n",
+            "Root MSE: 0.000\n",
+            "\n",
+            "Engine           Color  Games Resigned  Wrong  Drawn  Wrong             Time saved\n",
+            "stockfish1       White      4        1      0      0      0    0:00:01.464 (1.53%)\n",
+            "stockfish1       Black      0        0      0      0      0                      -\n",
+            "stockfish1       Total      4        1      0      0      0    0:00:01.464 (1.53%)\n",
+            "stockfish2       White      0        0      0      0      0                      -\n",
+            "stockfish2       Black      4        1      0      0      0    0:00:01.464 (1.53%)\n",
+            "stockfish2       Total      4        1      0      0      0    0:00:01.464 (1.53%)\n")
+    );
+}
+
+#[test]
+fn test_command_tablebase_rule() {
+    let output = run_command(
+        "test", "resign.pgn", &["none", "none", "--tablebase", "6", "--tablebase-savings"])
+        .unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "Games: 5\n",
+            "Adjudicated: 3 (0 wrong)\n",
+            "  Resign: 0 (0 wrong)\n",
+            "  Draw: 0 (0 wrong)\n",
+            "  Tablebase: 3\n",
+            "\n",
+            "Total Time: 0:01:57.986\n",
+            "After Adjudication: 0:01:41.155\n",
+            "Time saved: 0:00:16.831 (14.27%)\n",
+            "  Resign: 0:00:00.000 (0.00%)\n",
+            "  Draw: 0:00:00.000 (0.00%)\n",
+            "  Tablebase: 0:00:16.831 (14.27%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.000000\n",
+            "  Resign: 0.000000\n",
+            "  Draw: 0.000000\n",
+            "Root MSE: 0.000\n",
+            "\n",
+            "Pieces Adjudicated             Time saved       Total time saved\n",
+            "3                0    0:00:00.000 (0.00%)    0:00:00.000 (0.00%)\n",
+            "4                2    0:00:03.096 (2.62%)    0:00:03.096 (2.62%)\n",
+            "5                3   0:00:14.329 (12.14%)   0:00:14.329 (12.14%)\n",
+            "6                3   0:00:16.831 (14.27%)   0:00:16.831 (14.27%)\n",
+            "7                3   0:00:17.459 (14.80%)   0:00:17.459 (14.80%)\n")
+    );
+}
+
+#[test]
+fn test_command_draw_claims() {
+    let output = run_command("test", "claims.pgn", &["none", "none", "--draw-claims"]).unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "Games: 3\n",
+            "Adjudicated: 0 (0 wrong)\n",
+            "  Resign: 0 (0 wrong)\n",
+            "  Draw: 0 (0 wrong)\n",
+            "\n",
+            "Total Time: 0:00:06.000\n",
+            "After Adjudication: 0:00:06.000\n",
+            "Time saved: 0:00:00.000 (0.00%)\n",
+            "  Resign: 0:00:00.000 (0.00%)\n",
+            "  Draw: 0:00:00.000 (0.00%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Claimable draws: 3 (1 not drawn)\n",
+            "  Fifty moves: 1\n",
+            "  Repetition: 1\n",
+            "  Insufficient material: 1\n",
+            "Time after claims: 0:00:05.000 (83.33%)\n",
+            "\n",
+            "Mean Squared Error: 0.000000\n",
+            "  Resign: 0.000000\n",
+            "  Draw: 0.000000\n",
+            "Root MSE: 0.000\n")
+    );
+}
+
+#[test]
+fn test_command_invalid_game() {
+    let output = run_test("invalid.pgn", "none", "none", false).unwrap();
+
+    assert_eq!(output.stdout, "".to_string());
+    assert_eq!(output.stderr, "error: Game 2 has unknown result\n".to_string());
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_command_invalid_game_in_second_file() {
+    let output = run_in_input_dir(
+        &["test", "resign.pgn", "none", "none", "invalid.pgn", "--format", "json"]).unwrap();
+
+    assert_eq!(output.stdout, "".to_string());
+    assert_eq!(output.stderr, "error: invalid.pgn: Game 2 has unknown result\n".to_string());
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_command_illegal_move() {
+    let output = run_test("illegal.pgn", "none", "none", false).unwrap();
+
+    assert_eq!(output.stdout, "".to_string());
+    assert_eq!(output.stderr, "error: Game 1, Ply 2 - Illegal move\n".to_string());
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_command_skip_invalid() {
+    let output = run_command(
+        "test", "invalid.pgn", &["none", "none", "--verbose", "--skip-invalid"]).unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "game, actual_length, actual_time, actual_score, ",
+            "rule_applied, adjudicated_length, adjudicated_time, adjudicated_score\n",
+            "1, 7, 890, 1, -, 7, 890, 1\n",
+            "5, 2, 220, 0.5, -, 2, 220, 0.5\n",
+            "\n",
+            "Games: 2\n",
+            "Adjudicated: 0 (0 wrong)\n",
+            "  Resign: 0 (0 wrong)\n",
+            "  Draw: 0 (0 wrong)\n",
+            "\n",
+            "Total Time: 0:00:01.110\n",
+            "After Adjudication: 0:00:01.110\n",
+            "Time saved: 0:00:00.000 (0.00%)\n",
+            "  Resign: 0:00:00.000 (0.00%)\n",
+            "  Draw: 0:00:00.000 (0.00%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.000000\n",
+            "  Resign: 0.000000\n",
+            "  Draw: 0.000000\n",
+            "Root MSE: 0.000\n")
+    );
+    assert_eq!(
+        output.stderr,
+        concat!(
+            "Skipped 3 invalid games\n",
+            "  Unknown result: 1 (game 2)\n",
+            "  Missing comment: 1 (game 3)\n",
+            "  Bad comment format: 1 (game 4)\n")
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_command_skip_invalid_multiple_files() {
+    let output = run_in_input_dir(
+        &["test", "invalid.pgn", "resign.pgn", "--resign", "400/2", "--draw", "none",
+          "--verbose", "--skip-invalid"])
+        .unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "game, actual_length, actual_time, actual_score, ",
+            "rule_applied, adjudicated_length, adjudicated_time, adjudicated_score\n",
+            "invalid.pgn:1, 7, 890, 1, -, 7, 890, 1\n",
+            "invalid.pgn:5, 2, 220, 0.5, -, 2, 220, 0.5\n",
+            "resign.pgn:1, 73, 16590, 0.5, -, 73, 16590, 0.5\n",
+            "resign.pgn:2, 159, 22520, 1, R, 122, 21467, 1\n",
+            "resign.pgn:3, 160, 22432, 0, R, 125, 21105, 0\n",
+            "resign.pgn:4, 141, 22478, 0.5, -, 141, 22478, 0.5\n",
+            "resign.pgn:5, 512, 33966, 0.5, -, 512, 33966, 0.5\n",
+            "\n",
+            "Games: 7\n",
+            "Adjudicated: 2 (0 wrong)\n",
+            "  Resign: 2 (0 wrong)\n",
+            "  Draw: 0 (0 wrong)\n",
+            "\n",
+            "Total Time: 0:01:59.096\n",
+            "After Adjudication: 0:01:56.716\n",
+            "Time saved: 0:00:02.380 (2.00%)\n",
+            "  Resign: 0:00:02.380 (2.00%)\n",
+            "  Draw: 0:00:00.000 (0.00%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.000000\n",
+            "  Resign: 0.000000\n",
+            "  Draw: 0.000000\n",
+            "Root MSE: 0.000\n")
+    );
+    assert_eq!(
+        output.stderr,
+        concat!(
+            "Skipped 3 invalid games\n",
+            "  Unknown result: 1 (game invalid.pgn:2)\n",
+            "  Missing comment: 1 (game invalid.pgn:3)\n",
+            "  Bad comment format: 1 (game invalid.pgn:4)\n")
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_command_chess960() {
+    let output = run_test("chess960.pgn", "250/2", "none", true).unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "game, actual_length, actual_time, actual_score, ",
+            "rule_applied, adjudicated_length, adjudicated_time, adjudicated_score\n",
+            "1, 12, 1200, 1, R, 10, 1000, 1\n",
+            "2, 4, 400, 0.5, -, 4, 400, 0.5\n",
+            "\n",
+            "Games: 2\n",
+            "Adjudicated: 1 (0 wrong)\n",
+            "  Resign: 1 (0 wrong)\n",
+            "  Draw: 0 (0 wrong)\n",
+            "\n",
+            "Total Time: 0:00:01.600\n",
+            "After Adjudication: 0:00:01.400\n",
+            "Time saved: 0:00:00.200 (12.50%)\n",
+            "  Resign: 0:00:00.200 (12.50%)\n",
+            "  Draw: 0:00:00.000 (0.00%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.000000\n",
+            "  Resign: 0.000000\n",
+            "  Draw: 0.000000\n",
+            "Root MSE: 0.000\n")
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_command_rule_priority() {
+    let output = run_command(
+        "test", "resign.pgn", &["100/1", "30:400/3", "--verbose", "--priority", "resign"])
+        .unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "game, actual_length, actual_time, actual_score, ",
+            "rule_applied, adjudicated_length, adjudicated_time, adjudicated_score\n",
+            "1, 73, 16590, 0.5, R, 50, 14466, 1\n",
+            "2, 159, 22520, 1, R, 60, 13823, 1\n",
+            "3, 160, 22432, 0, D, 60, 13503, 0.5\n",
+            "4, 141, 22478, 0.5, D, 60, 15715, 0.5\n",
+            "5, 512, 33966, 0.5, R, 28, 7485, 1\n",
+            "\n",
+            "Games: 5\n",
+            "Adjudicated: 5 (3 wrong)\n",
+            "  Resign: 3 (2 wrong)\n",
+            "  Draw: 2 (1 wrong)\n",
+            "  Resign and draw on the same ply: 1 (scored as resign)\n",
+            "\n",
+            "Total Time: 0:01:57.986\n",
+            "After Adjudication: 0:01:04.992\n",
+            "Time saved: 0:00:15.460 (13.10%)\n",
+            "  Resign: 0:00:08.697 (7.37%)\n",
+            "  Draw: 0:00:06.763 (5.73%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.150000\n",
+            "  Resign: 0.100000\n",
+            "  Draw: 0.050000\n",
+            "Root MSE: 0.387\n")
+    );
+}
+
+#[test]
+fn test_command_wdl_rules() {
+    let output = run_test("draw.pgn", "L98/3", "34:D90/4", true).unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "game, actual_length, actual_time, actual_score, ",
+            "rule_applied, adjudicated_length, adjudicated_time, adjudicated_score\n",
+            "1, 55, 11406, 0.5, -, 55, 11406, 0.5\n",
+            "2, 73, 16862, 0.5, D, 68, 16423, 0.5\n",
+            "3, 73, 16590, 0.5, D, 68, 16220, 0.5\n",
+            "4, 151, 22138, 1, D, 68, 15652, 0.5\n",
+            "5, 190, 23480, 0, D, 74, 17595, 0.5\n",
+            "\n",
+            "Games: 5\n",
+            "Adjudicated: 4 (2 wrong)\n",
+            "  Resign: 0 (0 wrong)\n",
+            "  Draw: 4 (2 wrong)\n",
+            "\n",
+            "Total Time: 0:01:30.476\n",
+            "After Adjudication: 0:01:17.296\n",
+            "Time saved: 0:00:00.809 (0.89%)\n",
+            "  Resign: 0:00:00.000 (0.00%)\n",
+            "  Draw: 0:00:00.809 (0.89%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.100000\n",
+            "  Resign: 0.000000\n",
+            "  Draw: 0.100000\n",
+            "Root MSE: 0.316\n")
+    );
+}
+
+#[test]
+fn test_command_loss_percent_resign_rule() {
+    let output = run_test("resign.pgn", "L98/3", "none", true).unwrap();
+
+    assert_eq!(
+        output.stdout,
+        concat!(
+            "game, actual_length, actual_time, actual_score, ",
+            "rule_applied, adjudicated_length, adjudicated_time, adjudicated_score\n",
+            "1, 73, 16590, 0.5, -, 73, 16590, 0.5\n",
+            "2, 159, 22520, 1, R, 98, 19559, 1\n",
+            "3, 160, 22432, 0, R, 113, 20495, 0\n",
+            "4, 141, 22478, 0.5, R, 123, 21888, 0\n",
+            "5, 512, 33966, 0.5, R, 104, 19069, 1\n",
+            "\n",
+            "Games: 5\n",
+            "Adjudicated: 4 (2 wrong)\n",
+            "  Resign: 4 (2 wrong)\n",
+            "  Draw: 0 (0 wrong)\n",
+            "\n",
+            "Total Time: 0:01:57.986\n",
+            "After Adjudication: 0:01:37.601\n",
+            "Time saved: 0:00:04.898 (4.15%)\n",
+            "  Resign: 0:00:04.898 (4.15%)\n",
+            "  Draw: 0:00:00.000 (0.00%)\n",
+            "Note: 'Time saved' excludes incorrectly adjudicated games\n",
+            "\n",
+            "Mean Squared Error: 0.100000\n",
+            "  Resign: 0.100000\n",
+            "  Draw: 0.000000\n",
+            "Root MSE: 0.316\n")
+    );
+}